@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS escalation_settings;
+DROP TABLE IF EXISTS sessions;
+DROP TABLE IF EXISTS alerts;
+DROP TABLE IF EXISTS network_peers;
+DROP TABLE IF EXISTS trust_scores;
+DROP TABLE IF EXISTS request_peers;
+DROP TABLE IF EXISTS requests;
+DROP TABLE IF EXISTS users;
@@ -0,0 +1,89 @@
+-- Baseline schema. Every statement is idempotent so databases created by the
+-- old boot-time create_tables can adopt the migration history in place.
+
+CREATE TABLE IF NOT EXISTS users (
+    id UUID PRIMARY KEY,
+    username VARCHAR(100) NOT NULL UNIQUE,
+    email VARCHAR(255) NOT NULL UNIQUE,
+    password_hash VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL DEFAULT 'member',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS role VARCHAR(20) NOT NULL DEFAULT 'member';
+
+CREATE TABLE IF NOT EXISTS requests (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id),
+    title VARCHAR(255) NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    status VARCHAR(20) NOT NULL DEFAULT 'fair',
+    stalled_days INT NOT NULL DEFAULT 0,
+    document_id VARCHAR(100),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS request_peers (
+    id UUID PRIMARY KEY,
+    request_id UUID NOT NULL REFERENCES requests(id) ON DELETE CASCADE,
+    peer_name VARCHAR(100) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS trust_scores (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL UNIQUE REFERENCES users(id),
+    score INT NOT NULL DEFAULT 300,
+    status VARCHAR(20) NOT NULL DEFAULT 'Healthy',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS network_peers (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id),
+    peer_name VARCHAR(100) NOT NULL,
+    trust_level VARCHAR(20) NOT NULL DEFAULT 'Medium',
+    interactions INT NOT NULL DEFAULT 0,
+    last_interaction TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    position_x DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    position_y DOUBLE PRECISION NOT NULL DEFAULT 0.0
+);
+
+CREATE TABLE IF NOT EXISTS alerts (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id),
+    title VARCHAR(255) NOT NULL,
+    message TEXT NOT NULL DEFAULT '',
+    alert_type VARCHAR(20) NOT NULL DEFAULT 'system',
+    is_read BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Sessions used to be keyed by the plaintext token. Those rows can't be
+-- converted to hashed tokens, so the old table is dropped and every client
+-- signs in again once.
+DO $$ BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'sessions' AND column_name = 'token'
+    ) THEN
+        DROP TABLE sessions;
+    END IF;
+END $$;
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id UUID PRIMARY KEY,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    user_id UUID NOT NULL REFERENCES users(id),
+    user_agent VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS escalation_settings (
+    user_id UUID PRIMARY KEY REFERENCES users(id),
+    stalled_after_days INT NOT NULL DEFAULT 3,
+    critical_after_days INT NOT NULL DEFAULT 5,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
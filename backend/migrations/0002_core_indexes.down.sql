@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_sessions_user;
+DROP INDEX IF EXISTS idx_alerts_user_created;
+DROP INDEX IF EXISTS idx_requests_user_status;
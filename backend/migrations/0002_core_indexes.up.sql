@@ -0,0 +1,3 @@
+CREATE INDEX IF NOT EXISTS idx_requests_user_status ON requests (user_id, status);
+CREATE INDEX IF NOT EXISTS idx_alerts_user_created ON alerts (user_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions (user_id);
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_request_peers_request_peer;
+
+ALTER TABLE requests DROP COLUMN deleted_at;
+ALTER TABLE requests DROP COLUMN archived_at;
@@ -0,0 +1,10 @@
+ALTER TABLE requests ADD COLUMN archived_at TIMESTAMPTZ;
+ALTER TABLE requests ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- create_request never deduplicated peers, so collapse repeats before
+-- enforcing uniqueness.
+DELETE FROM request_peers a
+USING request_peers b
+WHERE a.request_id = b.request_id AND a.peer_name = b.peer_name AND a.id > b.id;
+
+CREATE UNIQUE INDEX idx_request_peers_request_peer ON request_peers (request_id, peer_name);
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS request_status_history;
+
+ALTER TABLE requests ALTER COLUMN status DROP DEFAULT;
+ALTER TABLE requests ALTER COLUMN status TYPE VARCHAR(20) USING status::text;
+ALTER TABLE requests ALTER COLUMN status SET DEFAULT 'fair';
+
+DROP TYPE IF EXISTS request_status;
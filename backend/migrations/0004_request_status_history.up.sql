@@ -0,0 +1,21 @@
+CREATE TYPE request_status AS ENUM ('fair', 'stalled', 'critical', 'completed');
+
+ALTER TABLE requests ALTER COLUMN status DROP DEFAULT;
+ALTER TABLE requests ALTER COLUMN status TYPE request_status USING status::request_status;
+ALTER TABLE requests ALTER COLUMN status SET DEFAULT 'fair';
+
+CREATE TABLE request_status_history (
+    id UUID PRIMARY KEY,
+    request_id UUID NOT NULL REFERENCES requests(id) ON DELETE CASCADE,
+    from_status request_status,
+    to_status request_status NOT NULL,
+    changed_by UUID REFERENCES users(id),
+    reason TEXT,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_request_status_history_request ON request_status_history (request_id, changed_at);
+
+-- Existing requests get a single entry for the status they currently hold.
+INSERT INTO request_status_history (id, request_id, from_status, to_status, changed_by, changed_at)
+SELECT gen_random_uuid(), id, NULL, status, user_id, updated_at FROM requests;
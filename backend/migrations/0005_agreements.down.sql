@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS agreement_revisions;
+DROP TABLE IF EXISTS agreements;
+
+DROP TYPE IF EXISTS agreement_party;
+DROP TYPE IF EXISTS agreement_status;
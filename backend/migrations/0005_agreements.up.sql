@@ -0,0 +1,30 @@
+CREATE TYPE agreement_status AS ENUM ('proposed', 'countered', 'accepted', 'rejected');
+CREATE TYPE agreement_party AS ENUM ('requester', 'peer');
+
+CREATE TABLE agreements (
+    id UUID PRIMARY KEY,
+    request_id UUID NOT NULL REFERENCES requests(id) ON DELETE CASCADE,
+    peer_name VARCHAR(100) NOT NULL,
+    status agreement_status NOT NULL DEFAULT 'proposed',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- A request has at most one agreement under negotiation at a time.
+CREATE UNIQUE INDEX idx_agreements_open_per_request ON agreements (request_id)
+    WHERE status IN ('proposed', 'countered');
+
+CREATE TABLE agreement_revisions (
+    id UUID PRIMARY KEY,
+    agreement_id UUID NOT NULL REFERENCES agreements(id) ON DELETE CASCADE,
+    version INT NOT NULL,
+    results TEXT NOT NULL,
+    guidelines TEXT NOT NULL,
+    resources TEXT NOT NULL,
+    accountability TEXT NOT NULL,
+    consequences TEXT NOT NULL,
+    proposed_by UUID NOT NULL REFERENCES users(id),
+    proposed_by_party agreement_party NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (agreement_id, version)
+);
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_network_peers_user;
+DROP INDEX IF EXISTS idx_requests_search;
+
+ALTER TABLE requests DROP COLUMN search_vector;
@@ -0,0 +1,5 @@
+ALTER TABLE requests ADD COLUMN search_vector tsvector
+    GENERATED ALWAYS AS (to_tsvector('english', title || ' ' || description)) STORED;
+
+CREATE INDEX idx_requests_search ON requests USING GIN (search_vector);
+CREATE INDEX idx_network_peers_user ON network_peers (user_id);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS trust_events;
+DROP TYPE IF EXISTS trust_event_type;
@@ -0,0 +1,43 @@
+CREATE TYPE trust_event_type AS ENUM (
+    'request_completed',
+    'request_stalled',
+    'request_critical',
+    'interaction',
+    'peer_added'
+);
+
+CREATE TABLE trust_events (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id),
+    event_type trust_event_type NOT NULL,
+    quantity INT NOT NULL DEFAULT 1,
+    points INT NOT NULL,
+    source_type VARCHAR(40),
+    source_id UUID,
+    description TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_trust_events_user_created ON trust_events (user_id, created_at);
+
+-- Seed the ledger from the state the old score was derived from, so existing
+-- scores carry over when the projection is first rebuilt.
+INSERT INTO trust_events (id, user_id, event_type, points, source_type, source_id, description, created_at)
+SELECT gen_random_uuid(), user_id,
+       CASE status WHEN 'completed' THEN 'request_completed'::trust_event_type
+                   WHEN 'stalled' THEN 'request_stalled'::trust_event_type
+                   ELSE 'request_critical'::trust_event_type END,
+       CASE status WHEN 'completed' THEN 20 WHEN 'stalled' THEN -15 ELSE -30 END,
+       'request', id, title, updated_at
+FROM requests
+WHERE status IN ('completed', 'stalled', 'critical') AND deleted_at IS NULL;
+
+INSERT INTO trust_events (id, user_id, event_type, points, source_type, source_id, description)
+SELECT gen_random_uuid(), user_id, 'peer_added', 10, 'network_peer', id, peer_name
+FROM network_peers;
+
+INSERT INTO trust_events (id, user_id, event_type, quantity, points, source_type, source_id, description, created_at)
+SELECT gen_random_uuid(), user_id, 'interaction', interactions, FLOOR(interactions * 1.5)::INT,
+       'network_peer', id, peer_name, last_interaction
+FROM network_peers
+WHERE interactions > 0;
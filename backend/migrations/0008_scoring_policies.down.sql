@@ -0,0 +1,2 @@
+ALTER TABLE trust_scores DROP COLUMN IF EXISTS policy_version;
+DROP TABLE IF EXISTS scoring_policies;
@@ -0,0 +1,27 @@
+CREATE TABLE scoring_policies (
+    version SERIAL PRIMARY KEY,
+    base_score INT NOT NULL,
+    completed_points INT NOT NULL,
+    stalled_penalty INT NOT NULL,
+    critical_penalty INT NOT NULL,
+    interaction_multiplier DOUBLE PRECISION NOT NULL,
+    peer_points INT NOT NULL,
+    healthy_threshold INT NOT NULL,
+    fair_threshold INT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT FALSE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (fair_threshold < healthy_threshold)
+);
+
+-- Exactly one policy scores users at any time.
+CREATE UNIQUE INDEX idx_scoring_policies_active ON scoring_policies (is_active) WHERE is_active;
+
+-- Version 1 is the policy that used to be hardcoded in compute_trust_score.
+INSERT INTO scoring_policies
+    (base_score, completed_points, stalled_penalty, critical_penalty,
+     interaction_multiplier, peer_points, healthy_threshold, fair_threshold, is_active)
+VALUES (300, 20, 15, 30, 1.5, 10, 400, 200, TRUE);
+
+ALTER TABLE trust_scores ADD COLUMN policy_version INT REFERENCES scoring_policies(version);
+UPDATE trust_scores SET policy_version = 1;
@@ -0,0 +1,15 @@
+CREATE TABLE trust_score_snapshots (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    score INT NOT NULL,
+    status VARCHAR(20) NOT NULL,
+    policy_version INT REFERENCES scoring_policies(version),
+    source VARCHAR(20) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_trust_score_snapshots_user_created ON trust_score_snapshots (user_id, created_at);
+
+INSERT INTO trust_score_snapshots (id, user_id, score, status, policy_version, source, created_at)
+SELECT gen_random_uuid(), user_id, score, status, policy_version, 'recalculation', COALESCE(updated_at, NOW())
+FROM trust_scores;
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS activity_events;
+DROP TYPE IF EXISTS activity_type;
+ALTER TABLE users DROP COLUMN IF EXISTS timezone;
+
+-- Postgres can't drop a value from an enum, so 'activity' stays on
+-- trust_event_type; only the ledger entries that used it are removed.
+DELETE FROM trust_events WHERE event_type = 'activity';
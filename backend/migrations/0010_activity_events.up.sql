@@ -0,0 +1,20 @@
+ALTER TYPE trust_event_type ADD VALUE IF NOT EXISTS 'activity';
+
+-- Daily activity caps reset at midnight in the user's own timezone.
+ALTER TABLE users ADD COLUMN timezone VARCHAR(64) NOT NULL DEFAULT 'UTC';
+
+CREATE TYPE activity_type AS ENUM ('chat_sent', 'question_asked', 'request_viewed', 'article_read');
+
+CREATE TABLE activity_events (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    client_event_id VARCHAR(100) NOT NULL,
+    activity_type activity_type NOT NULL,
+    target_id VARCHAR(100),
+    points INT NOT NULL,
+    activity_date DATE NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, client_event_id)
+);
+
+CREATE INDEX idx_activity_events_user_date ON activity_events (user_id, activity_date);
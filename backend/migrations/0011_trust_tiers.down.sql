@@ -0,0 +1,3 @@
+ALTER TABLE trust_scores DROP COLUMN IF EXISTS tier;
+ALTER TABLE scoring_policies DROP COLUMN IF EXISTS trusted_threshold, DROP COLUMN IF EXISTS tier_hysteresis;
+DROP TYPE IF EXISTS trust_tier;
@@ -0,0 +1,15 @@
+CREATE TYPE trust_tier AS ENUM ('critical', 'fair', 'trusted');
+
+ALTER TABLE scoring_policies
+    ADD COLUMN trusted_threshold INT NOT NULL DEFAULT 350,
+    ADD COLUMN tier_hysteresis INT NOT NULL DEFAULT 25;
+
+ALTER TABLE trust_scores ADD COLUMN tier trust_tier NOT NULL DEFAULT 'fair';
+
+UPDATE trust_scores ts SET tier = CASE
+        WHEN ts.score >= p.trusted_threshold THEN 'trusted'::trust_tier
+        WHEN ts.score >= p.fair_threshold THEN 'fair'::trust_tier
+        ELSE 'critical'::trust_tier
+    END
+FROM scoring_policies p
+WHERE p.is_active;
@@ -0,0 +1,4 @@
+ALTER TABLE scoring_policies DROP COLUMN IF EXISTS decay_grace_days, DROP COLUMN IF EXISTS decay_half_life_days;
+
+-- 'decay' stays on trust_event_type; Postgres can't drop enum values.
+DELETE FROM trust_events WHERE event_type = 'decay';
@@ -0,0 +1,8 @@
+ALTER TYPE trust_event_type ADD VALUE IF NOT EXISTS 'decay';
+
+-- Decay starts after decay_grace_days without engagement and halves the
+-- points above the base score every decay_half_life_days. A half-life of 0
+-- turns decay off.
+ALTER TABLE scoring_policies
+    ADD COLUMN decay_grace_days INT NOT NULL DEFAULT 14,
+    ADD COLUMN decay_half_life_days INT NOT NULL DEFAULT 60;
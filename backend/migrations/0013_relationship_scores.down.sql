@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_request_peers_peer_name;
+ALTER TABLE network_peers DROP COLUMN IF EXISTS relationship_score;
@@ -0,0 +1,5 @@
+-- trust_level is now derived from relationship_score; NULL until the
+-- relationship has been scored.
+ALTER TABLE network_peers ADD COLUMN relationship_score INT;
+
+CREATE INDEX idx_request_peers_peer_name ON request_peers (peer_name);
@@ -0,0 +1,17 @@
+-- One row per subject: the user themselves (peer_id NULL) or one of their
+-- network peers. `inputs` holds the numbers each wave was computed from.
+CREATE TABLE trust_wave_profiles (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    peer_id UUID REFERENCES network_peers(id) ON DELETE CASCADE,
+    self_trust INT NOT NULL,
+    relationship_trust INT NOT NULL,
+    organizational_trust INT NOT NULL,
+    market_trust INT NOT NULL,
+    societal_trust INT NOT NULL,
+    inputs JSONB NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_trust_wave_profiles_self ON trust_wave_profiles (user_id) WHERE peer_id IS NULL;
+CREATE UNIQUE INDEX idx_trust_wave_profiles_peer ON trust_wave_profiles (peer_id) WHERE peer_id IS NOT NULL;
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_network_peers_account;
+DROP INDEX IF EXISTS idx_network_peers_name;
+ALTER TABLE network_peers DROP COLUMN IF EXISTS peer_user_id;
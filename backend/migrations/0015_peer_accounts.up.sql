@@ -0,0 +1,22 @@
+-- A peer can be a registered user. Peers used to be matched to users by
+-- username, so those matches are linked in place.
+ALTER TABLE network_peers ADD COLUMN peer_user_id UUID REFERENCES users(id) ON DELETE SET NULL;
+
+UPDATE network_peers np SET peer_user_id = u.id
+FROM users u
+WHERE u.username = np.peer_name AND u.id <> np.user_id;
+
+-- Peer names are matched case-insensitively, so request tags are rewritten
+-- to the spelling of the owner's network peer.
+UPDATE request_peers rp SET peer_name = np.peer_name
+FROM requests r, network_peers np
+WHERE r.id = rp.request_id
+  AND np.user_id = r.user_id
+  AND LOWER(np.peer_name) = LOWER(rp.peer_name)
+  AND np.peer_name <> rp.peer_name
+  AND NOT EXISTS (
+      SELECT 1 FROM request_peers d WHERE d.request_id = rp.request_id AND d.peer_name = np.peer_name
+  );
+
+CREATE UNIQUE INDEX idx_network_peers_name ON network_peers (user_id, LOWER(peer_name));
+CREATE UNIQUE INDEX idx_network_peers_account ON network_peers (user_id, peer_user_id) WHERE peer_user_id IS NOT NULL;
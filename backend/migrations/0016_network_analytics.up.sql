@@ -0,0 +1,8 @@
+-- Network analytics are cached per user and reused until the fingerprint of
+-- the graph they were computed from changes.
+CREATE TABLE network_analytics_cache (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    graph_hash TEXT NOT NULL,
+    result JSONB NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS contact_syncs;
+ALTER TABLE users DROP COLUMN IF EXISTS contact_discovery;
+ALTER TABLE users DROP COLUMN IF EXISTS phone;
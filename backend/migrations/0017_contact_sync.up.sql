@@ -0,0 +1,15 @@
+-- Contact sync matches salted hashes of these against the hashes a device
+-- uploads. Users who opt out of discovery are never matched.
+ALTER TABLE users ADD COLUMN phone VARCHAR(16);
+ALTER TABLE users ADD COLUMN contact_discovery BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- One row per sync, for rate limiting. Uploaded identifiers aren't kept.
+CREATE TABLE contact_syncs (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    identifier_count INT NOT NULL,
+    match_count INT NOT NULL,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_contact_syncs_user ON contact_syncs (user_id, synced_at);
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_network_peers_pending_user;
+DROP INDEX IF EXISTS idx_network_peers_pending;
+ALTER TABLE network_peers DROP COLUMN IF EXISTS link_requested_at;
+ALTER TABLE network_peers DROP COLUMN IF EXISTS pending_user_id;
@@ -0,0 +1,12 @@
+-- Linking a peer to an account needs that account's consent: the link waits
+-- in pending_user_id until the invited user accepts it.
+ALTER TABLE network_peers ADD COLUMN pending_user_id UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE network_peers ADD COLUMN link_requested_at TIMESTAMPTZ;
+
+-- Existing links were made without asking, so they become requests.
+UPDATE network_peers
+SET pending_user_id = peer_user_id, peer_user_id = NULL, link_requested_at = NOW()
+WHERE peer_user_id IS NOT NULL;
+
+CREATE UNIQUE INDEX idx_network_peers_pending ON network_peers (user_id, pending_user_id) WHERE pending_user_id IS NOT NULL;
+CREATE INDEX idx_network_peers_pending_user ON network_peers (pending_user_id) WHERE pending_user_id IS NOT NULL;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_trust_events_ledger_key;
+ALTER TABLE trust_events DROP COLUMN IF EXISTS ledger_key;
@@ -0,0 +1,5 @@
+-- Events that may only be credited once carry a key; a second event with the
+-- same key for the same user is dropped.
+ALTER TABLE trust_events ADD COLUMN ledger_key TEXT;
+
+CREATE UNIQUE INDEX idx_trust_events_ledger_key ON trust_events (user_id, ledger_key) WHERE ledger_key IS NOT NULL;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_alerts_request;
+ALTER TABLE alerts DROP COLUMN IF EXISTS request_id;
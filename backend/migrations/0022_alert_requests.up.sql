@@ -0,0 +1,6 @@
+-- Request alerts point at their request, so they can be tied to the peers
+-- tagged on it without reading the message text. Older alerts can't be
+-- matched reliably and stay unlinked.
+ALTER TABLE alerts ADD COLUMN request_id UUID REFERENCES requests(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_alerts_request ON alerts (request_id) WHERE request_id IS NOT NULL;
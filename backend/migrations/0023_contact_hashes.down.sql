@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS contact_hash_state;
+DROP INDEX IF EXISTS idx_users_phone_hash;
+DROP INDEX IF EXISTS idx_users_email_hash;
+ALTER TABLE users DROP COLUMN IF EXISTS phone_hash;
+ALTER TABLE users DROP COLUMN IF EXISTS email_hash;
@@ -0,0 +1,13 @@
+-- Contact sync matches uploaded digests against these stored, salted hashes
+-- instead of hashing every user's identifiers on each sync. They're filled
+-- in by the server, which also records the salt they were made with so a new
+-- salt triggers a rehash.
+ALTER TABLE users ADD COLUMN email_hash TEXT;
+ALTER TABLE users ADD COLUMN phone_hash TEXT;
+
+CREATE INDEX idx_users_email_hash ON users (email_hash) WHERE email_hash IS NOT NULL;
+CREATE INDEX idx_users_phone_hash ON users (phone_hash) WHERE phone_hash IS NOT NULL;
+
+CREATE TABLE contact_hash_state (
+    salt TEXT NOT NULL
+);
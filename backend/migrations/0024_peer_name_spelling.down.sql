@@ -0,0 +1,3 @@
+-- The old spellings aren't kept, and the new ones are what 0015 meant to
+-- produce, so there's nothing to undo.
+SELECT 1;
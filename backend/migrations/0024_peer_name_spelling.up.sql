@@ -0,0 +1,20 @@
+-- 0015 respelled request tags to match their peer but kept a tag when the
+-- request already carried the peer's spelling, and left agreements alone.
+-- Those leftovers never match their peer, so the repeated tags go and
+-- agreements take the peer's spelling.
+DELETE FROM request_peers rp
+USING requests r, network_peers np
+WHERE r.id = rp.request_id
+  AND np.user_id = r.user_id
+  AND LOWER(np.peer_name) = LOWER(rp.peer_name)
+  AND np.peer_name <> rp.peer_name
+  AND EXISTS (
+      SELECT 1 FROM request_peers d WHERE d.request_id = rp.request_id AND d.peer_name = np.peer_name
+  );
+
+UPDATE agreements a SET peer_name = np.peer_name
+FROM requests r, network_peers np
+WHERE r.id = a.request_id
+  AND np.user_id = r.user_id
+  AND LOWER(np.peer_name) = LOWER(a.peer_name)
+  AND np.peer_name <> a.peer_name;
@@ -0,0 +1,3 @@
+-- 'repricing' stays on trust_event_type; Postgres can't drop enum values.
+-- The events only balance points, so scores are unaffected.
+DELETE FROM trust_events WHERE event_type = 'repricing';
@@ -0,0 +1,4 @@
+-- Activating a scoring policy balances each user's ledger with one
+-- repricing event instead of changing the points earlier events were
+-- recorded with.
+ALTER TYPE trust_event_type ADD VALUE IF NOT EXISTS 'repricing';
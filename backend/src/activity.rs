@@ -0,0 +1,267 @@
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{web, HttpResponse};
+use chrono::NaiveDate;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, FieldError};
+use crate::models::*;
+use crate::trust::{self, NewTrustEvent};
+
+const MAX_EVENTS_PER_BATCH: usize = 100;
+const MAX_ID_LENGTH: usize = 100;
+
+/// Activity types that share a daily allowance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ActivityCategory {
+    Chat,
+    Inquiry,
+    Listening,
+}
+
+impl ActivityCategory {
+    fn of(activity_type: ActivityType) -> Self {
+        match activity_type {
+            ActivityType::ChatSent => ActivityCategory::Chat,
+            ActivityType::QuestionAsked => ActivityCategory::Inquiry,
+            ActivityType::RequestViewed | ActivityType::ArticleRead => ActivityCategory::Listening,
+        }
+    }
+
+    fn points_per_event(self) -> i32 {
+        match self {
+            ActivityCategory::Chat => 1,
+            ActivityCategory::Inquiry => 4,
+            ActivityCategory::Listening => 7,
+        }
+    }
+
+    /// The most points the category can earn in one local day.
+    fn daily_cap(self) -> i32 {
+        match self {
+            ActivityCategory::Chat => 10,
+            ActivityCategory::Inquiry => 30,
+            ActivityCategory::Listening => 100,
+        }
+    }
+
+    /// Points the next event earns once `earned_today` points are in.
+    fn points_after(self, earned_today: i32) -> i32 {
+        self.points_per_event().min(self.daily_cap() - earned_today).max(0)
+    }
+}
+
+/// Passive activity only earns points the first time a target is seen each
+/// day, so reopening the same request repeatedly doesn't farm the cap.
+fn rewards_once_per_target(activity_type: ActivityType) -> bool {
+    ActivityCategory::of(activity_type) == ActivityCategory::Listening
+}
+
+fn validate_batch(batch: &ActivityBatch) -> Result<(), AppError> {
+    if batch.events.is_empty() {
+        return Err(AppError::invalid("events", "At least one event is required"));
+    }
+    if batch.events.len() > MAX_EVENTS_PER_BATCH {
+        return Err(AppError::invalid(
+            "events",
+            format!("At most {} events can be sent at once", MAX_EVENTS_PER_BATCH),
+        ));
+    }
+
+    let mut errors = Vec::new();
+    for (i, event) in batch.events.iter().enumerate() {
+        if event.id.trim().is_empty() || event.id.len() > MAX_ID_LENGTH {
+            errors.push(FieldError::new(
+                "events",
+                format!("Event {}: id must be between 1 and {} characters", i, MAX_ID_LENGTH),
+            ));
+        }
+        if event.target_id.as_ref().is_some_and(|t| t.len() > MAX_ID_LENGTH) {
+            errors.push(FieldError::new(
+                "events",
+                format!("Event {}: target_id must be at most {} characters", i, MAX_ID_LENGTH),
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+/// Records a batch of client activity. Each event is awarded its category's
+/// points until the category's daily cap is reached in the user's timezone;
+/// the total is then credited to the trust ledger as one `activity` event.
+pub async fn record_activity(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    body: web::Json<ActivityBatch>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    validate_batch(&body)?;
+
+    let mut tx = pool.begin().await?;
+
+    // Serialises concurrent batches for the same user so the caps hold.
+    let (today,): (NaiveDate,) = sqlx::query_as(
+        "SELECT (NOW() AT TIME ZONE timezone)::date FROM users WHERE id = $1 FOR UPDATE"
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let earned_rows: Vec<(ActivityType, i64)> = sqlx::query_as(
+        "SELECT activity_type, SUM(points) FROM activity_events
+         WHERE user_id = $1 AND activity_date = $2
+         GROUP BY activity_type"
+    )
+    .bind(user_id)
+    .bind(today)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut earned: HashMap<ActivityCategory, i32> = HashMap::new();
+    for (activity_type, points) in earned_rows {
+        *earned.entry(ActivityCategory::of(activity_type)).or_default() += points as i32;
+    }
+
+    let rewarded_rows: Vec<(ActivityType, String)> = sqlx::query_as(
+        "SELECT activity_type, target_id FROM activity_events
+         WHERE user_id = $1 AND activity_date = $2 AND target_id IS NOT NULL AND points > 0"
+    )
+    .bind(user_id)
+    .bind(today)
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut rewarded_targets: HashSet<(ActivityType, String)> = rewarded_rows.into_iter().collect();
+
+    let mut results = Vec::with_capacity(body.events.len());
+    let mut points_awarded = 0;
+
+    for event in &body.events {
+        let category = ActivityCategory::of(event.activity_type);
+        let earned_today = earned.entry(category).or_default();
+
+        let repeat = rewards_once_per_target(event.activity_type)
+            && event
+                .target_id
+                .as_ref()
+                .is_some_and(|t| rewarded_targets.contains(&(event.activity_type, t.clone())));
+
+        let points = if repeat {
+            0
+        } else {
+            category.points_after(*earned_today)
+        };
+
+        let inserted: Option<(Uuid,)> = sqlx::query_as(
+            "INSERT INTO activity_events (id, user_id, client_event_id, activity_type, target_id, points, activity_date)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT (user_id, client_event_id) DO NOTHING
+             RETURNING id"
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(&event.id)
+        .bind(event.activity_type)
+        .bind(&event.target_id)
+        .bind(points)
+        .bind(today)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let (outcome, points) = if inserted.is_none() {
+            (ActivityOutcome::Duplicate, 0)
+        } else if repeat {
+            (ActivityOutcome::Repeat, 0)
+        } else if points == 0 {
+            (ActivityOutcome::Capped, 0)
+        } else {
+            *earned_today += points;
+            points_awarded += points;
+            if let Some(target) = &event.target_id {
+                rewarded_targets.insert((event.activity_type, target.clone()));
+            }
+            (ActivityOutcome::Accepted, points)
+        };
+
+        results.push(ActivityResult {
+            id: event.id.clone(),
+            activity_type: event.activity_type,
+            outcome,
+            points,
+        });
+    }
+
+    let trust_score = if points_awarded > 0 {
+        let accepted = results.iter().filter(|r| r.outcome == ActivityOutcome::Accepted).count();
+        let description = format!("{} activity event(s) on {}", accepted, today);
+        let event = NewTrustEvent {
+            event_type: TrustEventType::Activity,
+            quantity: points_awarded,
+            source_type: "activity",
+            source_id: None,
+            description: &description,
+        };
+        Some(trust::record_event(&mut tx, user_id, event).await?)
+    } else {
+        None
+    };
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(ActivityResponse {
+        activity_date: today,
+        points_awarded,
+        results,
+        trust_score,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_earn_full_points_below_the_cap() {
+        assert_eq!(ActivityCategory::Chat.points_after(0), 1);
+        assert_eq!(ActivityCategory::Inquiry.points_after(8), 4);
+        assert_eq!(ActivityCategory::Listening.points_after(93), 7);
+    }
+
+    #[test]
+    fn the_last_event_before_the_cap_earns_only_the_remainder() {
+        assert_eq!(ActivityCategory::Inquiry.points_after(28), 2);
+        assert_eq!(ActivityCategory::Listening.points_after(99), 1);
+    }
+
+    #[test]
+    fn nothing_is_earned_at_or_past_the_cap() {
+        assert_eq!(ActivityCategory::Chat.points_after(10), 0);
+        assert_eq!(ActivityCategory::Inquiry.points_after(30), 0);
+        assert_eq!(ActivityCategory::Listening.points_after(140), 0);
+    }
+
+    #[test]
+    fn a_day_of_events_never_exceeds_the_cap() {
+        for category in [ActivityCategory::Chat, ActivityCategory::Inquiry, ActivityCategory::Listening] {
+            let mut earned = 0;
+            for _ in 0..100 {
+                earned += category.points_after(earned);
+            }
+            assert_eq!(earned, category.daily_cap(), "{:?}", category);
+        }
+    }
+
+    #[test]
+    fn only_passive_activity_is_rewarded_once_per_target() {
+        assert!(rewards_once_per_target(ActivityType::RequestViewed));
+        assert!(rewards_once_per_target(ActivityType::ArticleRead));
+        assert!(!rewards_once_per_target(ActivityType::ChatSent));
+        assert!(!rewards_once_per_target(ActivityType::QuestionAsked));
+    }
+}
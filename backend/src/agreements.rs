@@ -0,0 +1,325 @@
+use actix_web::{web, HttpResponse};
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, FieldError};
+use crate::models::*;
+use crate::relationships;
+
+/// How the caller relates to a request: its owner, or a registered user the
+/// owner's network links to one of its tagged peers.
+enum Party {
+    Requester,
+    Peer(String),
+}
+
+impl Party {
+    fn as_agreement_party(&self) -> AgreementParty {
+        match self {
+            Party::Requester => AgreementParty::Requester,
+            Party::Peer(_) => AgreementParty::Peer,
+        }
+    }
+
+    fn can_see(&self, agreement: &Agreement) -> bool {
+        match self {
+            Party::Requester => true,
+            Party::Peer(name) => *name == agreement.peer_name,
+        }
+    }
+}
+
+enum Response {
+    Accept,
+    Counter(AgreementTerms),
+    Reject,
+}
+
+fn validate_terms(terms: &AgreementTerms) -> Result<(), AppError> {
+    let fields = [
+        ("results", &terms.results),
+        ("guidelines", &terms.guidelines),
+        ("resources", &terms.resources),
+        ("accountability", &terms.accountability),
+        ("consequences", &terms.consequences),
+    ];
+
+    let errors: Vec<FieldError> = fields
+        .iter()
+        .filter(|(_, value)| value.trim().is_empty())
+        .map(|(field, _)| FieldError::new(field, "This element of the agreement is required"))
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+async fn resolve_party(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    request_id: Uuid,
+) -> Result<(Request, Party), AppError> {
+    let request = sqlx::query_as::<_, Request>(
+        "SELECT * FROM requests WHERE id = $1 AND deleted_at IS NULL"
+    )
+    .bind(request_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::NotFound("Request"))?;
+
+    if request.user_id == user_id {
+        return Ok((request, Party::Requester));
+    }
+
+    let peer: Option<(String,)> = sqlx::query_as(
+        "SELECT rp.peer_name FROM request_peers rp
+         JOIN network_peers np ON np.user_id = $3 AND np.peer_name = rp.peer_name
+         WHERE rp.request_id = $1 AND np.peer_user_id = $2"
+    )
+    .bind(request_id)
+    .bind(user_id)
+    .bind(request.user_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match peer {
+        Some((name,)) => Ok((request, Party::Peer(name))),
+        None => Err(AppError::NotFound("Request")),
+    }
+}
+
+async fn insert_revision(
+    conn: impl PgExecutor<'_>,
+    agreement_id: Uuid,
+    version: i32,
+    terms: &AgreementTerms,
+    proposed_by: Uuid,
+    party: AgreementParty,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO agreement_revisions
+            (id, agreement_id, version, results, guidelines, resources, accountability, consequences, proposed_by, proposed_by_party)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
+    )
+    .bind(Uuid::new_v4())
+    .bind(agreement_id)
+    .bind(version)
+    .bind(terms.results.trim())
+    .bind(terms.guidelines.trim())
+    .bind(terms.resources.trim())
+    .bind(terms.accountability.trim())
+    .bind(terms.consequences.trim())
+    .bind(proposed_by)
+    .bind(party)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn with_revisions(conn: impl PgExecutor<'_>, agreement: Agreement) -> Result<AgreementWithRevisions, sqlx::Error> {
+    let revisions = sqlx::query_as::<_, AgreementRevision>(
+        "SELECT * FROM agreement_revisions WHERE agreement_id = $1 ORDER BY version"
+    )
+    .bind(agreement.id)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(AgreementWithRevisions { agreement, revisions })
+}
+
+/// Whether `request_id` has an accepted agreement, which is required before
+/// the request can be completed.
+pub async fn has_accepted_agreement(conn: impl PgExecutor<'_>, request_id: Uuid) -> Result<bool, sqlx::Error> {
+    let row: (bool,) = sqlx::query_as(
+        "SELECT EXISTS (SELECT 1 FROM agreements WHERE request_id = $1 AND status = 'accepted')"
+    )
+    .bind(request_id)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(row.0)
+}
+
+pub async fn propose_agreement(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    body: web::Json<AgreementTerms>,
+) -> Result<HttpResponse, AppError> {
+    validate_terms(&body)?;
+
+    let mut tx = pool.begin().await?;
+    let (request, party) = resolve_party(&mut tx, user.id, path.into_inner()).await?;
+
+    let Party::Peer(peer_name) = party else {
+        return Err(AppError::Conflict("Agreements are proposed by a peer on the request".to_string()));
+    };
+    if request.archived_at.is_some() || request.status == RequestStatus::Completed {
+        return Err(AppError::Conflict("Agreements can only be proposed on open requests".to_string()));
+    }
+
+    let agreement = sqlx::query_as::<_, Agreement>(
+        "INSERT INTO agreements (id, request_id, peer_name) VALUES ($1, $2, $3) RETURNING *"
+    )
+    .bind(Uuid::new_v4())
+    .bind(request.id)
+    .bind(&peer_name)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db) if db.is_unique_violation() => {
+            AppError::Conflict("This request already has an agreement under negotiation".to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    insert_revision(&mut *tx, agreement.id, 1, &body, user.id, AgreementParty::Peer).await?;
+    let result = with_revisions(&mut *tx, agreement).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(result)))
+}
+
+pub async fn list_request_agreements(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.acquire().await?;
+    let (request, party) = resolve_party(&mut conn, user.id, path.into_inner()).await?;
+
+    let agreements = sqlx::query_as::<_, Agreement>(
+        "SELECT * FROM agreements WHERE request_id = $1 ORDER BY created_at DESC"
+    )
+    .bind(request.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut result = Vec::new();
+    for agreement in agreements.into_iter().filter(|a| party.can_see(a)) {
+        result.push(with_revisions(&mut *conn, agreement).await?);
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(result)))
+}
+
+pub async fn get_agreement(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.acquire().await?;
+
+    let agreement = sqlx::query_as::<_, Agreement>("SELECT * FROM agreements WHERE id = $1")
+        .bind(path.into_inner())
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(AppError::NotFound("Agreement"))?;
+
+    let (_, party) = resolve_party(&mut conn, user.id, agreement.request_id).await?;
+    if !party.can_see(&agreement) {
+        return Err(AppError::NotFound("Agreement"));
+    }
+
+    let result = with_revisions(&mut *conn, agreement).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(result)))
+}
+
+/// Applies the caller's response to the latest revision. Only the party that
+/// didn't author that revision may respond to it.
+async fn respond(
+    pool: &PgPool,
+    user_id: Uuid,
+    agreement_id: Uuid,
+    response: Response,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let agreement = sqlx::query_as::<_, Agreement>("SELECT * FROM agreements WHERE id = $1 FOR UPDATE")
+        .bind(agreement_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::NotFound("Agreement"))?;
+
+    let (request, party) = resolve_party(&mut tx, user_id, agreement.request_id).await?;
+    if !party.can_see(&agreement) {
+        return Err(AppError::NotFound("Agreement"));
+    }
+
+    match agreement.status {
+        AgreementStatus::Proposed | AgreementStatus::Countered => {}
+        AgreementStatus::Accepted => return Err(AppError::Conflict("This agreement has already been accepted".to_string())),
+        AgreementStatus::Rejected => return Err(AppError::Conflict("This agreement has already been rejected".to_string())),
+    }
+
+    let latest = sqlx::query_as::<_, AgreementRevision>(
+        "SELECT * FROM agreement_revisions WHERE agreement_id = $1 ORDER BY version DESC LIMIT 1"
+    )
+    .bind(agreement.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let caller = party.as_agreement_party();
+    if latest.proposed_by_party == caller {
+        return Err(AppError::Conflict("Waiting for the other party to respond".to_string()));
+    }
+
+    let status = match &response {
+        Response::Accept => AgreementStatus::Accepted,
+        Response::Counter(terms) => {
+            insert_revision(&mut *tx, agreement.id, latest.version + 1, terms, user_id, caller).await?;
+            AgreementStatus::Countered
+        }
+        Response::Reject => AgreementStatus::Rejected,
+    };
+
+    let agreement = sqlx::query_as::<_, Agreement>(
+        "UPDATE agreements SET status = $1, updated_at = NOW() WHERE id = $2 RETURNING *"
+    )
+    .bind(status)
+    .bind(agreement.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if status != AgreementStatus::Countered {
+        relationships::refresh_relationship_scores(&mut tx, Some(request.user_id)).await?;
+    }
+
+    let result = with_revisions(&mut *tx, agreement).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(result)))
+}
+
+pub async fn accept_agreement(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    respond(pool.get_ref(), user.id, path.into_inner(), Response::Accept).await
+}
+
+pub async fn counter_agreement(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    body: web::Json<AgreementTerms>,
+) -> Result<HttpResponse, AppError> {
+    validate_terms(&body)?;
+    respond(pool.get_ref(), user.id, path.into_inner(), Response::Counter(body.into_inner())).await
+}
+
+pub async fn reject_agreement(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    respond(pool.get_ref(), user.id, path.into_inner(), Response::Reject).await
+}
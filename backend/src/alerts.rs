@@ -1,56 +1,68 @@
-use actix_web::{web, HttpRequest, HttpResponse};
-use sqlx::PgPool;
+use actix_web::{web, HttpResponse};
+use sqlx::{PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
-use crate::auth::get_user_from_token;
+use crate::auth::AuthenticatedUser;
+use crate::error::AppError;
 use crate::models::*;
+use crate::pagination::{Keyed, Page, SortKey};
+
+const ALERT_SORT_KEYS: &[SortKey] = &[
+    SortKey { name: "created_at", expr: "created_at", sql_type: "timestamptz", default_desc: true },
+    SortKey { name: "title", expr: "title", sql_type: "text", default_desc: false },
+];
+
+fn push_alert_filters<'a>(qb: &mut QueryBuilder<'a, Postgres>, user_id: Uuid, query: &'a AlertFilter) {
+    qb.push(" FROM alerts WHERE user_id = ").push_bind(user_id);
+
+    if let Some(f) = query.filter.as_deref().filter(|f| *f != "all") {
+        qb.push(" AND alert_type = ").push_bind(f);
+    }
+    if let Some(is_read) = query.is_read {
+        qb.push(" AND is_read = ").push_bind(is_read);
+    }
+    if let Some(from) = query.from {
+        qb.push(" AND created_at >= ").push_bind(from);
+    }
+    if let Some(to) = query.to {
+        qb.push(" AND created_at < ").push_bind(to);
+    }
+}
 
 pub async fn list_alerts(
     pool: web::Data<PgPool>,
-    req: HttpRequest,
+    user: AuthenticatedUser,
     query: web::Query<AlertFilter>,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let page = Page::new(
+        ALERT_SORT_KEYS,
+        query.sort.as_deref(),
+        query.order.as_deref(),
+        query.limit,
+        query.cursor.as_deref(),
+    )?;
 
-    let alerts = match &query.filter {
-        Some(f) if f != "all" => {
-            sqlx::query_as::<_, Alert>(
-                "SELECT * FROM alerts WHERE user_id = $1 AND alert_type = $2 ORDER BY created_at DESC"
-            )
-            .bind(user_id)
-            .bind(f)
-            .fetch_all(pool.get_ref())
-            .await
-        }
-        _ => {
-            sqlx::query_as::<_, Alert>(
-                "SELECT * FROM alerts WHERE user_id = $1 ORDER BY created_at DESC"
-            )
-            .bind(user_id)
-            .fetch_all(pool.get_ref())
-            .await
-        }
-    };
+    let mut count_qb = QueryBuilder::new("SELECT COUNT(*)");
+    push_alert_filters(&mut count_qb, user_id, &query);
+    let (total,): (i64,) = count_qb.build_query_as().fetch_one(pool.get_ref()).await?;
 
-    match alerts {
-        Ok(a) => HttpResponse::Ok().json(ApiResponse::ok(a)),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Error: {}", e))),
-    }
+    let mut qb = QueryBuilder::new("");
+    page.select(&mut qb, "*");
+    push_alert_filters(&mut qb, user_id, &query);
+    page.push_bounds(&mut qb, "id");
+    let rows: Vec<Keyed<Alert>> = qb.build_query_as().fetch_all(pool.get_ref()).await?;
+    let (alerts, meta) = page.finish(rows, total, |a| a.id);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::paginated(alerts, meta)))
 }
 
 pub async fn mark_alert_read(
     pool: web::Data<PgPool>,
-    req: HttpRequest,
+    user: AuthenticatedUser,
     path: web::Path<Uuid>,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
-
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
     let alert_id = path.into_inner();
 
     let result = sqlx::query(
@@ -59,13 +71,11 @@ pub async fn mark_alert_read(
     .bind(alert_id)
     .bind(user_id)
     .execute(pool.get_ref())
-    .await;
+    .await?;
 
-    match result {
-        Ok(r) if r.rows_affected() > 0 => {
-            HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"marked_read": true})))
-        }
-        Ok(_) => HttpResponse::NotFound().json(ApiResponse::<()>::err("Alert not found")),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Error: {}", e))),
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Alert"));
     }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"marked_read": true}))))
 }
@@ -0,0 +1,152 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::AppError;
+use crate::models::*;
+
+/// Raw measures for one window, as computed by `window_metrics`.
+#[derive(Debug, FromRow)]
+struct WindowMetrics {
+    requests_initiated: i32,
+    agreements_proposed: i32,
+    follow_ups: i32,
+    active_requests: i32,
+    completed_requests: i32,
+    mean_hours_to_complete: Option<f64>,
+    stalled_requests: i32,
+    stalled_hours: f64,
+    critical_hours: f64,
+    points_lost: i32,
+}
+
+impl WindowMetrics {
+    fn completion_rate(&self) -> Option<f64> {
+        (self.active_requests > 0).then(|| self.completed_requests as f64 / self.active_requests as f64)
+    }
+
+    fn stalled_ratio(&self) -> Option<f64> {
+        (self.active_requests > 0).then(|| self.stalled_requests as f64 / self.active_requests as f64)
+    }
+}
+
+/// Measures the user's requests over `[from, to)`. A request is active in the
+/// window if it existed before the window ended and wasn't completed before it
+/// started. Time spent stalled or critical is taken from the status history,
+/// clipped to the window.
+async fn window_metrics(
+    pool: &PgPool,
+    user_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<WindowMetrics, sqlx::Error> {
+    sqlx::query_as::<_, WindowMetrics>(
+        "WITH reqs AS (
+             SELECT r.id, r.created_at,
+                    (SELECT MIN(h.changed_at) FROM request_status_history h
+                     WHERE h.request_id = r.id AND h.to_status = 'completed') AS completed_at
+             FROM requests r
+             WHERE r.user_id = $1 AND r.deleted_at IS NULL
+         ),
+         spans AS (
+             SELECT h.request_id, h.to_status, h.changed_at AS span_start,
+                    COALESCE(LEAD(h.changed_at) OVER (PARTITION BY h.request_id ORDER BY h.changed_at, h.id), NOW()) AS span_end
+             FROM request_status_history h
+             JOIN reqs ON reqs.id = h.request_id
+         ),
+         overlap AS (
+             SELECT request_id, to_status,
+                    GREATEST(LEAST(span_end, $3) - GREATEST(span_start, $2), INTERVAL '0') AS duration
+             FROM spans
+         )
+         SELECT
+             (SELECT COUNT(*) FROM reqs WHERE created_at >= $2 AND created_at < $3)::int AS requests_initiated,
+             (SELECT COUNT(*) FROM agreement_revisions
+              WHERE proposed_by = $1 AND version = 1 AND created_at >= $2 AND created_at < $3)::int AS agreements_proposed,
+             (SELECT COUNT(*) FROM request_status_history h JOIN reqs ON reqs.id = h.request_id
+              WHERE h.changed_by = $1 AND h.from_status IS NOT NULL
+                AND h.changed_at >= $2 AND h.changed_at < $3)::int AS follow_ups,
+             (SELECT COUNT(*) FROM reqs
+              WHERE created_at < $3 AND (completed_at IS NULL OR completed_at >= $2))::int AS active_requests,
+             (SELECT COUNT(*) FROM reqs WHERE completed_at >= $2 AND completed_at < $3)::int AS completed_requests,
+             (SELECT AVG(EXTRACT(EPOCH FROM completed_at - created_at)) / 3600 FROM reqs
+              WHERE completed_at >= $2 AND completed_at < $3)::float8 AS mean_hours_to_complete,
+             (SELECT COUNT(DISTINCT request_id) FROM overlap
+              WHERE to_status IN ('stalled', 'critical') AND duration > INTERVAL '0')::int AS stalled_requests,
+             (COALESCE((SELECT SUM(EXTRACT(EPOCH FROM duration)) FROM overlap WHERE to_status = 'stalled'), 0) / 3600)::float8
+                 AS stalled_hours,
+             (COALESCE((SELECT SUM(EXTRACT(EPOCH FROM duration)) FROM overlap WHERE to_status = 'critical'), 0) / 3600)::float8
+                 AS critical_hours,
+             COALESCE((SELECT -SUM(points) FROM trust_events
+                       WHERE user_id = $1 AND points < 0 AND created_at >= $2 AND created_at < $3), 0)::int AS points_lost"
+    )
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .fetch_one(pool)
+    .await
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn compare(current: Option<f64>, previous: Option<f64>) -> MetricComparison {
+    let current = current.map(round2);
+    let previous = previous.map(round2);
+    let change = current.zip(previous).map(|(c, p)| round2(c - p));
+    let change_pct = current
+        .zip(previous)
+        .filter(|(_, p)| *p != 0.0)
+        .map(|(c, p)| round2((c - p) / p * 100.0));
+
+    MetricComparison { current, previous, change, change_pct }
+}
+
+fn compare_counts(current: i32, previous: i32) -> MetricComparison {
+    compare(Some(current as f64), Some(previous as f64))
+}
+
+pub async fn get_summary(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<AnalyticsQuery>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let period = query.period.unwrap_or_default();
+    let length = Duration::days(period.days());
+
+    let to = Utc::now();
+    let from = to - length;
+    let previous_from = from - length;
+
+    let cur = window_metrics(pool.get_ref(), user_id, from, to).await?;
+    let prev = window_metrics(pool.get_ref(), user_id, previous_from, from).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(AnalyticsSummary {
+        period,
+        current: DateRange { from, to },
+        previous: DateRange { from: previous_from, to: from },
+        lead: LeadMeasures {
+            requests_initiated: compare_counts(cur.requests_initiated, prev.requests_initiated),
+            agreements_proposed: compare_counts(cur.agreements_proposed, prev.agreements_proposed),
+            follow_ups: compare_counts(cur.follow_ups, prev.follow_ups),
+        },
+        lag: LagMeasures {
+            completion_rate: compare(cur.completion_rate(), prev.completion_rate()),
+            mean_hours_to_complete: compare(cur.mean_hours_to_complete, prev.mean_hours_to_complete),
+            stalled_ratio: compare(cur.stalled_ratio(), prev.stalled_ratio()),
+        },
+        trust_tax: TrustTax {
+            stalled_hours: compare(Some(cur.stalled_hours), Some(prev.stalled_hours)),
+            critical_hours: compare(Some(cur.critical_hours), Some(prev.critical_hours)),
+            total_hours: compare(
+                Some(cur.stalled_hours + cur.critical_hours),
+                Some(prev.stalled_hours + prev.critical_hours),
+            ),
+            points_lost: compare_counts(cur.points_lost, prev.points_lost),
+        },
+    })))
+}
@@ -1,8 +1,26 @@
-use actix_web::{web, HttpRequest, HttpResponse};
-use sqlx::PgPool;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::time::Duration;
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use sha2::{Digest, Sha256};
+use sqlx::{PgExecutor, PgPool};
 use uuid::Uuid;
 
+use crate::contacts;
+use crate::error::{AppError, FieldError};
 use crate::models::*;
+use crate::trust;
+
+/// A session that hasn't been used for this long is treated as expired.
+const SESSION_IDLE_TIMEOUT_DAYS: i32 = 7;
+/// Hard upper bound on a session's lifetime, regardless of activity.
+const SESSION_MAX_AGE_DAYS: i32 = 30;
+
+const SESSION_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 fn generate_token() -> String {
     use rand::Rng;
@@ -19,147 +37,407 @@ fn generate_token() -> String {
         .collect()
 }
 
-pub async fn get_user_from_token(pool: &PgPool, req: &HttpRequest) -> Option<Uuid> {
-    let token = req
-        .headers()
+/// Sessions are keyed by the SHA-256 of the bearer token so a leaked
+/// `sessions` table can't be replayed against the API.
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
         .get("Authorization")?
         .to_str()
         .ok()?
-        .strip_prefix("Bearer ")?
-        .to_string();
+        .strip_prefix("Bearer ")
+}
+
+async fn create_session(conn: impl PgExecutor<'_>, user_id: Uuid, req: &HttpRequest) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    let user_agent = req
+        .headers()
+        .get("User-Agent")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.chars().take(255).collect::<String>());
+
+    sqlx::query(
+        "INSERT INTO sessions (id, token_hash, user_id, user_agent, expires_at)
+         VALUES ($1, $2, $3, $4, NOW() + make_interval(days => $5))"
+    )
+    .bind(Uuid::new_v4())
+    .bind(hash_token(&token))
+    .bind(user_id)
+    .bind(user_agent)
+    .bind(SESSION_MAX_AGE_DAYS)
+    .execute(conn)
+    .await?;
+
+    Ok(token)
+}
+
+/// The user behind the request's bearer token. Taking this as a handler
+/// argument is what makes an endpoint authenticated: the session is resolved
+/// once per request and cached in the request extensions, and a missing or
+/// expired session is rejected with a 401 before the handler runs.
+///
+/// Use `Option<AuthenticatedUser>` for endpoints where signing in is optional,
+/// and `RequireRole<R>` for endpoints restricted to a role.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub id: Uuid,
+    pub role: String,
+    pub session_id: Uuid,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = AppError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            authenticate(&req).await?.ok_or(AppError::Unauthorized)
+        })
+    }
+}
+
+pub trait Role {
+    const NAME: &'static str;
+}
 
-    let row: Option<(Uuid,)> =
-        sqlx::query_as("SELECT user_id FROM sessions WHERE token = $1")
-            .bind(&token)
-            .fetch_optional(pool)
-            .await
-            .ok()?;
+pub struct Admin;
 
-    row.map(|r| r.0)
+impl Role for Admin {
+    const NAME: &'static str = "admin";
+}
+
+/// An `AuthenticatedUser` whose role is `R`. Signed-in users with another
+/// role are rejected with a 403.
+pub struct RequireRole<R: Role> {
+    pub user: AuthenticatedUser,
+    _role: PhantomData<R>,
+}
+
+pub type AdminUser = RequireRole<Admin>;
+
+impl<R: Role> Deref for RequireRole<R> {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &AuthenticatedUser {
+        &self.user
+    }
+}
+
+impl<R: Role + 'static> FromRequest for RequireRole<R> {
+    type Error = AppError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let user = authenticate(&req).await?.ok_or(AppError::Unauthorized)?;
+
+            if user.role != R::NAME {
+                return Err(AppError::Forbidden);
+            }
+
+            Ok(Self { user, _role: PhantomData })
+        })
+    }
+}
+
+async fn authenticate(req: &HttpRequest) -> Result<Option<AuthenticatedUser>, AppError> {
+    if let Some(cached) = req.extensions().get::<Option<AuthenticatedUser>>() {
+        return Ok(cached.clone());
+    }
+
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .ok_or_else(|| AppError::Internal("database pool is not configured".to_string()))?;
+    let user = resolve_session(pool.get_ref(), req).await?;
+    req.extensions_mut().insert(user.clone());
+    Ok(user)
+}
+
+async fn resolve_session(pool: &PgPool, req: &HttpRequest) -> Result<Option<AuthenticatedUser>, sqlx::Error> {
+    let token_hash = match bearer_token(req) {
+        Some(token) => hash_token(token),
+        None => return Ok(None),
+    };
+
+    let row: Option<(Uuid, String, Uuid)> = sqlx::query_as(
+        "UPDATE sessions s SET last_seen_at = NOW()
+         FROM users u
+         WHERE u.id = s.user_id
+           AND s.token_hash = $1
+           AND s.expires_at > NOW()
+           AND s.last_seen_at > NOW() - make_interval(days => $2)
+         RETURNING s.user_id, u.role, s.id"
+    )
+    .bind(&token_hash)
+    .bind(SESSION_IDLE_TIMEOUT_DAYS)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|(id, role, session_id)| AuthenticatedUser { id, role, session_id }))
+}
+
+pub async fn purge_expired_sessions(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "DELETE FROM sessions
+         WHERE expires_at <= NOW() OR last_seen_at <= NOW() - make_interval(days => $1)"
+    )
+    .bind(SESSION_IDLE_TIMEOUT_DAYS)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Deletes expired and idle sessions once per `SESSION_PURGE_INTERVAL`.
+pub fn spawn_session_purge(pool: PgPool) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(SESSION_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_expired_sessions(&pool).await {
+                Ok(0) => {}
+                Ok(n) => println!("Session purge: {} expired session(s) removed", n),
+                Err(e) => eprintln!("Session purge failed: {}", e),
+            }
+        }
+    });
 }
 
 pub async fn register(
     pool: web::Data<PgPool>,
+    req: HttpRequest,
     body: web::Json<RegisterRequest>,
-) -> HttpResponse {
+) -> Result<HttpResponse, AppError> {
+    let mut errors = Vec::new();
+
     if body.username.len() < 3 {
-        return HttpResponse::BadRequest().json(ApiResponse::<()>::err("Username must be at least 3 characters"));
+        errors.push(FieldError::new("username", "Username must be at least 3 characters"));
     }
 
     let email_regex = regex::Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
     if !email_regex.is_match(&body.email) {
-        return HttpResponse::BadRequest().json(ApiResponse::<()>::err("Invalid email format"));
+        errors.push(FieldError::new("email", "Invalid email format"));
     }
 
     if body.password.len() < 8 {
-        return HttpResponse::BadRequest().json(ApiResponse::<()>::err("Password must be at least 8 characters"));
+        errors.push(FieldError::new("password", "Password must be at least 8 characters"));
+    }
+
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
     }
 
     let existing: Option<(Uuid,)> =
         sqlx::query_as("SELECT id FROM users WHERE email = $1")
             .bind(&body.email)
             .fetch_optional(pool.get_ref())
-            .await
-            .unwrap_or(None);
+            .await?;
 
     if existing.is_some() {
-        return HttpResponse::Conflict().json(ApiResponse::<()>::err("Email already registered"));
+        return Err(AppError::Conflict("Email already registered".to_string()));
     }
 
-    let password_hash = match bcrypt::hash(&body.password, 10) {
-        Ok(h) => h,
-        Err(_) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::err("Failed to hash password")),
-    };
-
+    let password_hash = bcrypt::hash(&body.password, 10)?;
     let user_id = Uuid::new_v4();
 
-    let result = sqlx::query_as::<_, User>(
+    let mut tx = pool.begin().await?;
+
+    let user = sqlx::query_as::<_, User>(
         "INSERT INTO users (id, username, email, password_hash) VALUES ($1, $2, $3, $4) RETURNING *"
     )
     .bind(user_id)
     .bind(&body.username)
     .bind(&body.email)
     .bind(&password_hash)
-    .fetch_one(pool.get_ref())
-    .await;
-
-    match result {
-        Ok(user) => {
-            sqlx::query("INSERT INTO trust_scores (id, user_id, score, status) VALUES ($1, $2, 300, 'Healthy')")
-                .bind(Uuid::new_v4())
-                .bind(user_id)
-                .execute(pool.get_ref())
-                .await
-                .ok();
-
-            let token = generate_token();
-            sqlx::query("INSERT INTO sessions (token, user_id) VALUES ($1, $2)")
-                .bind(&token)
-                .bind(user_id)
-                .execute(pool.get_ref())
-                .await
-                .ok();
-
-            HttpResponse::Ok().json(ApiResponse::ok(AuthResponse {
-                user: user.into(),
-                token,
-            }))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db) if db.is_unique_violation() => {
+            AppError::Conflict("Username or email already registered".to_string())
         }
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Registration failed: {}", e))),
-    }
+        _ => e.into(),
+    })?;
+
+    contacts::store_identifier_hashes(&mut *tx, user_id, &user.email, user.phone.as_deref()).await?;
+    trust::rebuild_trust_score(&mut tx, user_id).await?;
+
+    let token = create_session(&mut *tx, user_id, &req).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(AuthResponse {
+        user: user.into(),
+        token,
+    })))
 }
 
 pub async fn login(
     pool: web::Data<PgPool>,
+    req: HttpRequest,
     body: web::Json<LoginRequest>,
-) -> HttpResponse {
+) -> Result<HttpResponse, AppError> {
     let user = sqlx::query_as::<_, User>(
         "SELECT * FROM users WHERE email = $1"
     )
     .bind(&body.email)
     .fetch_optional(pool.get_ref())
-    .await;
+    .await?
+    .ok_or(AppError::InvalidCredentials)?;
 
-    match user {
-        Ok(Some(user)) => {
-            if !bcrypt::verify(&body.password, &user.password_hash).unwrap_or(false) {
-                return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Invalid email or password"));
-            }
-
-            let token = generate_token();
-            sqlx::query("INSERT INTO sessions (token, user_id) VALUES ($1, $2)")
-                .bind(&token)
-                .bind(user.id)
-                .execute(pool.get_ref())
-                .await
-                .ok();
-
-            HttpResponse::Ok().json(ApiResponse::ok(AuthResponse {
-                user: user.into(),
-                token,
-            }))
-        }
-        Ok(None) => HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Invalid email or password")),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Login failed: {}", e))),
+    if !bcrypt::verify(&body.password, &user.password_hash).unwrap_or(false) {
+        return Err(AppError::InvalidCredentials);
     }
+
+    let token = create_session(pool.get_ref(), user.id, &req).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(AuthResponse {
+        user: user.into(),
+        token,
+    })))
 }
 
 pub async fn me(
     pool: web::Data<PgPool>,
-    req: HttpRequest,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
 
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
         .bind(user_id)
         .fetch_optional(pool.get_ref())
-        .await;
+        .await?
+        .ok_or(AppError::NotFound("User"))?;
+
+    let tier: Option<(TrustTier,)> = sqlx::query_as("SELECT tier FROM trust_scores WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    let mut response = UserResponse::from(user);
+    response.tier = tier.map(|(tier,)| tier);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(response)))
+}
+
+pub async fn logout(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    sqlx::query("DELETE FROM sessions WHERE id = $1")
+        .bind(user.session_id)
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"logged_out": true}))))
+}
+
+pub async fn logout_all(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+
+    let result = sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+        .bind(user_id)
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"sessions_revoked": result.rows_affected()}))))
+}
+
+pub async fn list_sessions(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
 
-    match user {
-        Ok(Some(user)) => HttpResponse::Ok().json(ApiResponse::ok(UserResponse::from(user))),
-        _ => HttpResponse::NotFound().json(ApiResponse::<()>::err("User not found")),
+    let sessions = sqlx::query_as::<_, SessionInfo>(
+        "SELECT id, user_agent, created_at, last_seen_at, expires_at, id = $2 AS current
+         FROM sessions
+         WHERE user_id = $1
+           AND expires_at > NOW()
+           AND last_seen_at > NOW() - make_interval(days => $3)
+         ORDER BY last_seen_at DESC"
+    )
+    .bind(user_id)
+    .bind(user.session_id)
+    .bind(SESSION_IDLE_TIMEOUT_DAYS)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(sessions)))
+}
+
+pub async fn update_timezone(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    body: web::Json<TimezoneSettings>,
+) -> Result<HttpResponse, AppError> {
+    let (known,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)")
+        .bind(&body.timezone)
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    if !known {
+        return Err(AppError::invalid("timezone", "Unknown timezone"));
     }
+
+    let user = sqlx::query_as::<_, User>("UPDATE users SET timezone = $1 WHERE id = $2 RETURNING *")
+        .bind(&body.timezone)
+        .bind(user.id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or(AppError::NotFound("User"))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(UserResponse::from(user))))
+}
+
+/// Strips common separators and checks the result is an E.164 number.
+fn normalize_phone(raw: &str) -> Option<String> {
+    let phone: String = raw.chars().filter(|c| !matches!(c, ' ' | '-' | '(' | ')' | '.')).collect();
+    let digits = phone.strip_prefix('+')?;
+    let valid = (8..=15).contains(&digits.len())
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && !digits.starts_with('0');
+    valid.then_some(phone)
+}
+
+pub async fn update_contact_discovery(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    body: web::Json<ContactDiscoverySettings>,
+) -> Result<HttpResponse, AppError> {
+    let phone = match &body.phone {
+        Some(Some(raw)) => Some(Some(
+            normalize_phone(raw).ok_or_else(|| AppError::invalid("phone", "Phone must be in E.164 format, e.g. +14155550123"))?,
+        )),
+        Some(None) => Some(None),
+        None => None,
+    };
+
+    let mut tx = pool.begin().await?;
+
+    let user = sqlx::query_as::<_, User>(
+        "UPDATE users SET
+            contact_discovery = COALESCE($1, contact_discovery),
+            phone = CASE WHEN $2 THEN $3 ELSE phone END
+         WHERE id = $4 RETURNING *"
+    )
+    .bind(body.contact_discovery)
+    .bind(phone.is_some())
+    .bind(phone.flatten())
+    .bind(user.id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound("User"))?;
+
+    contacts::store_identifier_hashes(&mut *tx, user.id, &user.email, user.phone.as_deref()).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(UserResponse::from(user))))
 }
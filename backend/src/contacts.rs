@@ -0,0 +1,277 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, FieldError};
+use crate::models::*;
+
+const DEFAULT_SALT: &str = "trust-os-contacts-v1";
+const MAX_IDENTIFIERS_PER_SYNC: usize = 1000;
+const MAX_SYNCS_PER_HOUR: i64 = 5;
+const MAX_IDENTIFIERS_PER_DAY: i64 = 5000;
+
+/// The salt devices hash identifiers with. It only keeps uploaded hashes
+/// from matching other services' tables, so it isn't secret.
+fn contact_salt() -> String {
+    std::env::var("CONTACT_HASH_SALT").unwrap_or_else(|_| DEFAULT_SALT.to_string())
+}
+
+/// `hex(sha256(salt + ":" + identifier))`, the digest devices upload.
+fn identifier_hash(salt: &str, identifier: &str) -> String {
+    hex::encode(Sha256::digest(format!("{}:{}", salt, identifier).as_bytes()))
+}
+
+fn email_hash(salt: &str, email: &str) -> String {
+    identifier_hash(salt, &email.trim().to_lowercase())
+}
+
+/// Call whenever the user's email or phone changes.
+pub async fn store_identifier_hashes(
+    conn: impl PgExecutor<'_>,
+    user_id: Uuid,
+    email: &str,
+    phone: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let salt = contact_salt();
+    sqlx::query("UPDATE users SET email_hash = $1, phone_hash = $2 WHERE id = $3")
+        .bind(email_hash(&salt, email))
+        .bind(phone.map(|p| identifier_hash(&salt, p)))
+        .bind(user_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Fills in missing identifier hashes, rehashing everyone if the salt changed.
+pub async fn refresh_identifier_hashes(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let salt = contact_salt();
+    let mut tx = pool.begin().await?;
+
+    let stored: Option<(String,)> = sqlx::query_as("SELECT salt FROM contact_hash_state FOR UPDATE")
+        .fetch_optional(&mut *tx)
+        .await?;
+    if stored.as_ref().map(|(s,)| s.as_str()) != Some(salt.as_str()) {
+        sqlx::query("UPDATE users SET email_hash = NULL, phone_hash = NULL")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM contact_hash_state").execute(&mut *tx).await?;
+        sqlx::query("INSERT INTO contact_hash_state (salt) VALUES ($1)")
+            .bind(&salt)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let stale: Vec<(Uuid, String, Option<String>)> = sqlx::query_as(
+        "SELECT id, email, phone FROM users
+         WHERE email_hash IS NULL OR (phone IS NOT NULL AND phone_hash IS NULL)"
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let ids: Vec<Uuid> = stale.iter().map(|(id, _, _)| *id).collect();
+    let email_hashes: Vec<String> = stale.iter().map(|(_, email, _)| email_hash(&salt, email)).collect();
+    let phone_hashes: Vec<Option<String>> = stale
+        .iter()
+        .map(|(_, _, phone)| phone.as_deref().map(|p| identifier_hash(&salt, p)))
+        .collect();
+
+    sqlx::query(
+        "UPDATE users u SET email_hash = h.email_hash, phone_hash = h.phone_hash
+         FROM UNNEST($1::uuid[], $2::text[], $3::text[]) AS h(id, email_hash, phone_hash)
+         WHERE u.id = h.id"
+    )
+    .bind(&ids)
+    .bind(&email_hashes)
+    .bind(&phone_hashes)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(stale.len())
+}
+
+fn is_sha256_hex(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Lowercases and de-duplicates the hashes.
+fn normalize_hashes(hashes: &[String], field: &'static str, errors: &mut Vec<FieldError>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(hashes.len());
+    for (i, hash) in hashes.iter().enumerate() {
+        let hash = hash.trim().to_ascii_lowercase();
+        if !is_sha256_hex(&hash) {
+            errors.push(FieldError::new(field, format!("Entry {}: expected a SHA-256 hex digest", i)));
+        } else if !normalized.contains(&hash) {
+            normalized.push(hash);
+        }
+    }
+    normalized
+}
+
+pub async fn get_contact_hashing(_user: AuthenticatedUser) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(ContactHashing {
+        algorithm: "sha256",
+        encoding: "hex",
+        salt: contact_salt(),
+        email_preimage: "<salt>:<email, trimmed and lowercased>",
+        phone_preimage: "<salt>:<phone in E.164, e.g. +14155550123>",
+        max_identifiers_per_sync: MAX_IDENTIFIERS_PER_SYNC,
+    })))
+}
+
+/// Suggests registered users whose hashes match the caller's contacts.
+/// Uploaded hashes are never stored, only counted for rate limiting.
+pub async fn sync_contacts(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    body: web::Json<ContactSyncBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+
+    let mut errors = Vec::new();
+    let email_hashes = normalize_hashes(&body.email_hashes, "email_hashes", &mut errors);
+    let phone_hashes = normalize_hashes(&body.phone_hashes, "phone_hashes", &mut errors);
+    let identifier_count = email_hashes.len() + phone_hashes.len();
+    if errors.is_empty() && identifier_count == 0 {
+        errors.push(FieldError::new("email_hashes", "At least one hashed identifier is required"));
+    }
+    if identifier_count > MAX_IDENTIFIERS_PER_SYNC {
+        errors.push(FieldError::new(
+            "email_hashes",
+            format!("At most {} identifiers can be synced at once", MAX_IDENTIFIERS_PER_SYNC),
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let (syncs_last_hour, oldest_last_hour, identifiers_last_day): (i64, Option<DateTime<Utc>>, i64) = sqlx::query_as(
+        "SELECT COUNT(*) FILTER (WHERE synced_at > NOW() - INTERVAL '1 hour'),
+                MIN(synced_at) FILTER (WHERE synced_at > NOW() - INTERVAL '1 hour'),
+                COALESCE(SUM(identifier_count), 0)
+         FROM contact_syncs
+         WHERE user_id = $1 AND synced_at > NOW() - INTERVAL '1 day'"
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if syncs_last_hour >= MAX_SYNCS_PER_HOUR {
+        let retry_after_secs = oldest_last_hour
+            .map_or(3600, |oldest| 3600 - (Utc::now() - oldest).num_seconds())
+            .max(1);
+        return Err(AppError::RateLimited { retry_after_secs });
+    }
+    if identifiers_last_day + identifier_count as i64 > MAX_IDENTIFIERS_PER_DAY {
+        return Err(AppError::RateLimited { retry_after_secs: 3600 });
+    }
+
+    let matches: Vec<(ContactSuggestion, bool)> = sqlx::query_as::<_, (Uuid, String, bool, bool, bool)>(
+        "SELECT u.id, u.username, COALESCE(u.email_hash = ANY($1), FALSE), COALESCE(u.phone_hash = ANY($2), FALSE),
+                np.id IS NOT NULL
+         FROM users u
+         LEFT JOIN network_peers np ON np.user_id = $3 AND u.id IN (np.peer_user_id, np.pending_user_id)
+         WHERE (u.email_hash = ANY($1) OR u.phone_hash = ANY($2)) AND u.id <> $3 AND u.contact_discovery
+         ORDER BY u.username"
+    )
+    .bind(&email_hashes)
+    .bind(&phone_hashes)
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|(id, username, matched_email, matched_phone, in_network)| {
+        let suggestion = ContactSuggestion { user_id: id, username, matched_email, matched_phone };
+        (suggestion, in_network)
+    })
+    .collect();
+
+    sqlx::query(
+        "INSERT INTO contact_syncs (id, user_id, identifier_count, match_count) VALUES ($1, $2, $3, $4)"
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(identifier_count as i32)
+    .bind(matches.len() as i32)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let already_in_network = matches.iter().filter(|(_, in_network)| *in_network).count() as i32;
+    let suggestions = matches
+        .into_iter()
+        .filter(|(_, in_network)| !in_network)
+        .map(|(s, _)| s)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(ContactSyncResult {
+        suggestions,
+        already_in_network,
+        syncs_remaining: (MAX_SYNCS_PER_HOUR - syncs_last_hour - 1) as i32,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADA: &str = "4d35c68fbf806244dc7ac3b5d4f38edd9bcf2018b7fc88e943916d6544a226e7";
+    const PHONE: &str = "37efbe951142fe15f69f859e52bce4779b31aac562f010b7fb15c2f34d086879";
+
+    #[test]
+    fn identifier_hash_matches_the_documented_preimage() {
+        assert_eq!(identifier_hash(DEFAULT_SALT, "ada@example.com"), ADA);
+        assert_eq!(identifier_hash(DEFAULT_SALT, "+14155550123"), PHONE);
+        assert_ne!(identifier_hash("another-salt", "ada@example.com"), ADA);
+    }
+
+    #[test]
+    fn email_hash_ignores_case_and_surrounding_space() {
+        assert_eq!(email_hash(DEFAULT_SALT, "  Ada@Example.COM "), ADA);
+    }
+
+    #[test]
+    fn normalize_hashes_lowercases_and_drops_duplicates() {
+        let mut errors = Vec::new();
+        let hashes = [ADA.to_uppercase(), format!(" {} ", ADA), PHONE.to_string()];
+
+        let normalized = normalize_hashes(&hashes, "email_hashes", &mut errors);
+        assert!(errors.is_empty());
+        assert_eq!(normalized, vec![ADA.to_string(), PHONE.to_string()]);
+    }
+
+    #[test]
+    fn normalize_hashes_reports_each_malformed_entry() {
+        let mut errors = Vec::new();
+        let hashes = [
+            ADA.to_string(),
+            "ada@example.com".to_string(),
+            ADA[..63].to_string(),
+            format!("{}g", &ADA[..63]),
+        ];
+
+        let normalized = normalize_hashes(&hashes, "phone_hashes", &mut errors);
+        assert_eq!(normalized, vec![ADA.to_string()]);
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Entry 1: expected a SHA-256 hex digest",
+                "Entry 2: expected a SHA-256 hex digest",
+                "Entry 3: expected a SHA-256 hex digest",
+            ]
+        );
+        assert!(errors.iter().all(|e| e.field == "phone_hashes"));
+    }
+}
@@ -1,70 +1,213 @@
-use sqlx::PgPool;
-
-pub async fn create_tables(pool: &PgPool) -> Result<(), sqlx::Error> {
-    let tables = vec![
-        r#"CREATE TABLE IF NOT EXISTS users (
-            id UUID PRIMARY KEY,
-            username VARCHAR(100) NOT NULL UNIQUE,
-            email VARCHAR(255) NOT NULL UNIQUE,
-            password_hash VARCHAR(255) NOT NULL,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        )"#,
-        r#"CREATE TABLE IF NOT EXISTS requests (
-            id UUID PRIMARY KEY,
-            user_id UUID NOT NULL REFERENCES users(id),
-            title VARCHAR(255) NOT NULL,
-            description TEXT NOT NULL DEFAULT '',
-            status VARCHAR(20) NOT NULL DEFAULT 'fair',
-            stalled_days INT NOT NULL DEFAULT 0,
-            document_id VARCHAR(100),
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        )"#,
-        r#"CREATE TABLE IF NOT EXISTS request_peers (
-            id UUID PRIMARY KEY,
-            request_id UUID NOT NULL REFERENCES requests(id) ON DELETE CASCADE,
-            peer_name VARCHAR(100) NOT NULL
-        )"#,
-        r#"CREATE TABLE IF NOT EXISTS trust_scores (
-            id UUID PRIMARY KEY,
-            user_id UUID NOT NULL UNIQUE REFERENCES users(id),
-            score INT NOT NULL DEFAULT 300,
-            status VARCHAR(20) NOT NULL DEFAULT 'Healthy',
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        )"#,
-        r#"CREATE TABLE IF NOT EXISTS network_peers (
-            id UUID PRIMARY KEY,
-            user_id UUID NOT NULL REFERENCES users(id),
-            peer_name VARCHAR(100) NOT NULL,
-            trust_level VARCHAR(20) NOT NULL DEFAULT 'Medium',
-            interactions INT NOT NULL DEFAULT 0,
-            last_interaction TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            position_x DOUBLE PRECISION NOT NULL DEFAULT 0.0,
-            position_y DOUBLE PRECISION NOT NULL DEFAULT 0.0
-        )"#,
-        r#"CREATE TABLE IF NOT EXISTS alerts (
-            id UUID PRIMARY KEY,
-            user_id UUID NOT NULL REFERENCES users(id),
-            title VARCHAR(255) NOT NULL,
-            message TEXT NOT NULL DEFAULT '',
-            alert_type VARCHAR(20) NOT NULL DEFAULT 'system',
-            is_read BOOLEAN NOT NULL DEFAULT FALSE,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        )"#,
-        r#"CREATE TABLE IF NOT EXISTS sessions (
-            token VARCHAR(64) PRIMARY KEY,
-            user_id UUID NOT NULL REFERENCES users(id),
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        )"#,
-    ];
+use sqlx::{Executor, PgPool};
 
-    for table_sql in tables {
-        sqlx::query(table_sql).execute(pool).await?;
-    }
+use crate::models::{RequestStatus, TrustEventType};
+use crate::relationships;
+use crate::trust::{self, NewTrustEvent};
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    up: &'static str,
+    down: &'static str,
+    /// Rolling back removes ledger events, so scores must be rebuilt.
+    rewrites_ledger: bool,
+}
+
+macro_rules! migration {
+    ($version:expr, $name:literal) => {
+        migration!($version, $name, false)
+    };
+    ($version:expr, $name:literal, rewrites_ledger) => {
+        migration!($version, $name, true)
+    };
+    ($version:expr, $name:literal, $rewrites_ledger:expr) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../migrations/", $name, ".up.sql")),
+            down: include_str!(concat!("../migrations/", $name, ".down.sql")),
+            rewrites_ledger: $rewrites_ledger,
+        }
+    };
+}
+
+/// Every schema change, in order. Add new entries at the end; never edit or
+/// renumber one that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_initial_schema"),
+    migration!(2, "0002_core_indexes"),
+    migration!(3, "0003_request_lifecycle"),
+    migration!(4, "0004_request_status_history"),
+    migration!(5, "0005_agreements"),
+    migration!(6, "0006_list_search"),
+    migration!(7, "0007_trust_events"),
+    migration!(8, "0008_scoring_policies"),
+    migration!(9, "0009_trust_score_snapshots"),
+    migration!(10, "0010_activity_events", rewrites_ledger),
+    migration!(11, "0011_trust_tiers"),
+    migration!(12, "0012_trust_decay", rewrites_ledger),
+    migration!(13, "0013_relationship_scores"),
+    migration!(14, "0014_trust_waves"),
+    migration!(15, "0015_peer_accounts"),
+    migration!(16, "0016_network_analytics"),
+    migration!(17, "0017_contact_sync"),
+    migration!(18, "0018_peer_link_consent"),
+    migration!(19, "0019_trust_event_keys"),
+    migration!(22, "0022_alert_requests"),
+    migration!(23, "0023_contact_hashes"),
+    migration!(24, "0024_peer_name_spelling"),
+    migration!(25, "0025_repricing_events"),
+];
 
+/// Arbitrary key for `pg_advisory_lock` so two servers booting at once don't
+/// apply the same migration twice.
+const MIGRATION_LOCK_KEY: i64 = 0x7472_7573_745f_6f73;
+
+async fn ensure_migrations_table(conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .await?;
     Ok(())
 }
 
+/// Applies every migration newer than the database's current version, each
+/// in its own transaction. Returns the versions that were applied.
+pub async fn run_migrations(pool: &PgPool) -> Result<Vec<i64>, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    let result = async {
+        ensure_migrations_table(&mut conn).await?;
+
+        let applied: Vec<(i64,)> = sqlx::query_as("SELECT version FROM schema_migrations")
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut newly_applied = Vec::new();
+        for migration in MIGRATIONS {
+            if applied.iter().any(|(v,)| *v == migration.version) {
+                continue;
+            }
+
+            let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+            tx.execute(migration.up).await?;
+            sqlx::query("INSERT INTO schema_migrations (version, name) VALUES ($1, $2)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            println!("Applied migration {}", migration.name);
+            newly_applied.push(migration.version);
+        }
+
+        Ok(newly_applied)
+    }
+    .await;
+
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    result
+}
+
+/// Reverts the most recently applied migration. Returns its version, or
+/// `None` if nothing has been applied.
+pub async fn rollback_last_migration(pool: &PgPool) -> Result<Option<i64>, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    let result = async {
+        ensure_migrations_table(&mut conn).await?;
+
+        let latest: Option<(i64,)> = sqlx::query_as("SELECT version FROM schema_migrations ORDER BY version DESC LIMIT 1")
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        let Some((version,)) = latest else {
+            return Ok(None);
+        };
+
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.version == version)
+            .ok_or_else(|| sqlx::Error::Protocol(format!("no migration with version {} in this build", version)))?;
+
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        tx.execute(migration.down).await?;
+        if migration.rewrites_ledger {
+            // The schema may no longer match this build, so the scores are
+            // only marked here and rebuilt by the server at startup.
+            tx.execute("UPDATE trust_scores SET policy_version = NULL").await?;
+        }
+        sqlx::query("DELETE FROM schema_migrations WHERE version = $1")
+            .bind(version)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        println!("Reverted migration {}", migration.name);
+        Ok(Some(version))
+    }
+    .await;
+
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    result
+}
+
+/// Promotes an existing account to admin. Admins are only ever created this
+/// way, from the command line, never through the API or the demo seed.
+pub async fn grant_admin(pool: &PgPool, email: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE users SET role = 'admin' WHERE email = $1")
+        .bind(email.trim())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Connects to the scratch database named by `TEST_DATABASE_URL` and brings
+/// its schema up to date. Tests that need a database are skipped when the
+/// variable isn't set.
+#[cfg(test)]
+pub async fn test_pool() -> Option<PgPool> {
+    let url = std::env::var("TEST_DATABASE_URL").ok()?;
+    let pool = PgPool::connect(&url).await.expect("Failed to connect to TEST_DATABASE_URL");
+    run_migrations(&pool).await.expect("Failed to run migrations");
+    Some(pool)
+}
+
+/// Registers a throwaway member account for a test.
+#[cfg(test)]
+pub async fn create_test_user(pool: &PgPool) -> uuid::Uuid {
+    let id = uuid::Uuid::new_v4();
+    sqlx::query("INSERT INTO users (id, username, email, password_hash) VALUES ($1, $2, $3, '')")
+        .bind(id)
+        .bind(format!("test-{}", id))
+        .bind(format!("{}@test.invalid", id))
+        .execute(pool)
+        .await
+        .expect("Failed to create test user");
+    id
+}
+
 pub async fn seed_demo_data(pool: &PgPool) -> Result<(), sqlx::Error> {
     let user_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users")
         .fetch_one(pool)
@@ -78,7 +221,7 @@ pub async fn seed_demo_data(pool: &PgPool) -> Result<(), sqlx::Error> {
     let password_hash = bcrypt::hash("demo1234", 10).unwrap();
 
     sqlx::query(
-        "INSERT INTO users (id, username, email, password_hash) VALUES ($1, $2, $3, $4)"
+        "INSERT INTO users (id, username, email, password_hash, role) VALUES ($1, $2, $3, $4, 'member')"
     )
     .bind(demo_user_id)
     .bind("demo.user")
@@ -87,28 +230,21 @@ pub async fn seed_demo_data(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
-    sqlx::query(
-        "INSERT INTO trust_scores (id, user_id, score, status) VALUES ($1, $2, $3, $4)"
-    )
-    .bind(uuid::Uuid::new_v4())
-    .bind(demo_user_id)
-    .bind(320)
-    .bind("Healthy")
-    .execute(pool)
-    .await?;
+    let mut conn = pool.acquire().await?;
 
     let req_ids: Vec<uuid::Uuid> = (0..5).map(|_| uuid::Uuid::new_v4()).collect();
     let requests = vec![
-        (&req_ids[0], "Q3 Budget Alignment", "Align team spending with quarterly targets and submit final budget proposal.", "critical", 7, Some("DOC-4521")),
-        (&req_ids[1], "Onboarding Checklist for New Hire", "Complete the onboarding checklist for the new team member starting next Monday.", "stalled", 3, None),
-        (&req_ids[2], "Weekly Sync Feedback Loop", "Establish a recurring feedback loop during weekly syncs to track action items.", "fair", 0, None),
-        (&req_ids[3], "Client Deliverable Review", "Review and approve the client deliverable before the Friday deadline.", "stalled", 4, Some("DOC-3387")),
-        (&req_ids[4], "Team Trust Retrospective", "Schedule and facilitate a trust-building retrospective for the engineering team.", "fair", 0, None),
+        (&req_ids[0], "Q3 Budget Alignment", "Align team spending with quarterly targets and submit final budget proposal.", RequestStatus::Critical, 7, Some("DOC-4521")),
+        (&req_ids[1], "Onboarding Checklist for New Hire", "Complete the onboarding checklist for the new team member starting next Monday.", RequestStatus::Stalled, 3, None),
+        (&req_ids[2], "Weekly Sync Feedback Loop", "Establish a recurring feedback loop during weekly syncs to track action items.", RequestStatus::Fair, 0, None),
+        (&req_ids[3], "Client Deliverable Review", "Review and approve the client deliverable before the Friday deadline.", RequestStatus::Stalled, 4, Some("DOC-3387")),
+        (&req_ids[4], "Team Trust Retrospective", "Schedule and facilitate a trust-building retrospective for the engineering team.", RequestStatus::Fair, 0, None),
     ];
 
     for (id, title, desc, status, stalled, doc) in &requests {
         sqlx::query(
-            "INSERT INTO requests (id, user_id, title, description, status, stalled_days, document_id) VALUES ($1, $2, $3, $4, $5, $6, $7)"
+            "INSERT INTO requests (id, user_id, title, description, status, stalled_days, document_id, updated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, NOW() - make_interval(days => $6))"
         )
         .bind(*id)
         .bind(demo_user_id)
@@ -119,6 +255,29 @@ pub async fn seed_demo_data(pool: &PgPool) -> Result<(), sqlx::Error> {
         .bind(*doc)
         .execute(pool)
         .await?;
+
+        sqlx::query(
+            "INSERT INTO request_status_history (id, request_id, to_status, changed_by, changed_at)
+             VALUES ($1, $2, $3, $4, NOW() - make_interval(days => $5))"
+        )
+        .bind(uuid::Uuid::new_v4())
+        .bind(*id)
+        .bind(*status)
+        .bind(demo_user_id)
+        .bind(*stalled)
+        .execute(pool)
+        .await?;
+
+        for event_type in trust::status_events(None, *status) {
+            let event = NewTrustEvent {
+                event_type: *event_type,
+                quantity: 1,
+                source_type: "request",
+                source_id: Some(**id),
+                description: title,
+            };
+            trust::append_event(&mut conn, demo_user_id, event).await?;
+        }
     }
 
     let peers_data = vec![
@@ -152,10 +311,11 @@ pub async fn seed_demo_data(pool: &PgPool) -> Result<(), sqlx::Error> {
     ];
 
     for (name, level, interactions, px, py) in &network {
+        let peer_id = uuid::Uuid::new_v4();
         sqlx::query(
             "INSERT INTO network_peers (id, user_id, peer_name, trust_level, interactions, position_x, position_y) VALUES ($1, $2, $3, $4, $5, $6, $7)"
         )
-        .bind(uuid::Uuid::new_v4())
+        .bind(peer_id)
         .bind(demo_user_id)
         .bind(*name)
         .bind(*level)
@@ -164,25 +324,52 @@ pub async fn seed_demo_data(pool: &PgPool) -> Result<(), sqlx::Error> {
         .bind(*py)
         .execute(pool)
         .await?;
+
+        let added = NewTrustEvent {
+            event_type: TrustEventType::PeerAdded,
+            quantity: 1,
+            source_type: "network_peer",
+            source_id: Some(peer_id),
+            description: name,
+        };
+        trust::append_event(&mut conn, demo_user_id, added).await?;
+
+        let interacted = NewTrustEvent {
+            event_type: TrustEventType::Interaction,
+            quantity: *interactions,
+            source_type: "network_peer",
+            source_id: Some(peer_id),
+            description: name,
+        };
+        trust::append_event(&mut conn, demo_user_id, interacted).await?;
     }
 
+    relationships::refresh_relationship_scores(&mut conn, Some(demo_user_id)).await?;
+
+    let score = trust::rebuild_trust_score(&mut conn, demo_user_id).await?;
+    let score_message = format!(
+        "Your Emotional Bank Account score has been recalculated: {} ({}).",
+        score.score, score.status
+    );
+
     let alerts = vec![
-        ("Budget Request Critical", "Q3 Budget Alignment has been stalled for 7 days and is now critical.", "request"),
-        ("New Team Member Joining", "Jordan Blake's onboarding checklist needs attention before Monday.", "request"),
-        ("Weekly Sync Scheduled", "Your weekly sync feedback loop is set for Thursday at 2pm.", "system"),
-        ("Trust Score Updated", "Your Emotional Bank Account score has been recalculated: 320 (Healthy).", "system"),
-        ("Deliverable Deadline Approaching", "Client Deliverable Review is due this Friday. Status: Stalled.", "request"),
+        ("Budget Request Critical", "Q3 Budget Alignment has been stalled for 7 days and is now critical.", "request", Some(req_ids[0])),
+        ("New Team Member Joining", "Jordan Blake's onboarding checklist needs attention before Monday.", "request", Some(req_ids[1])),
+        ("Weekly Sync Scheduled", "Your weekly sync feedback loop is set for Thursday at 2pm.", "system", None),
+        ("Trust Score Updated", score_message.as_str(), "system", None),
+        ("Deliverable Deadline Approaching", "Client Deliverable Review is due this Friday. Status: Stalled.", "request", Some(req_ids[3])),
     ];
 
-    for (title, msg, atype) in &alerts {
+    for (title, msg, atype, request_id) in &alerts {
         sqlx::query(
-            "INSERT INTO alerts (id, user_id, title, message, alert_type) VALUES ($1, $2, $3, $4, $5)"
+            "INSERT INTO alerts (id, user_id, title, message, alert_type, request_id) VALUES ($1, $2, $3, $4, $5, $6)"
         )
         .bind(uuid::Uuid::new_v4())
         .bind(demo_user_id)
         .bind(*title)
         .bind(*msg)
         .bind(*atype)
+        .bind(*request_id)
         .execute(pool)
         .await?;
     }
@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AdminUser;
+use crate::error::AppError;
+use crate::models::*;
+use crate::trust::{self, NewTrustEvent};
+
+const DECAY_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Debug, FromRow)]
+struct DecayCandidate {
+    user_id: Uuid,
+    score: i32,
+    last_engaged_at: DateTime<Utc>,
+    /// Decay already charged since `last_engaged_at`.
+    streak_decay: i64,
+}
+
+/// The total decay (zero or negative) a user should have been charged for
+/// the current stretch of inactivity. Only points above `base_score` decay,
+/// halving every `half_life_days` once `grace_days` have passed.
+pub fn decay_target(undecayed_score: i32, base_score: i32, days_inactive: f64, grace_days: i32, half_life_days: i32) -> i32 {
+    let decaying_days = days_inactive - grace_days as f64;
+    if half_life_days <= 0 || decaying_days <= 0.0 || undecayed_score <= base_score {
+        return 0;
+    }
+
+    let remaining = 0.5_f64.powf(decaying_days / half_life_days as f64);
+    -((undecayed_score - base_score) as f64 * (1.0 - remaining)).floor() as i32
+}
+
+/// Charges inactivity decay to every user who has gone longer than the
+/// active policy's grace period without engaging. Decay is computed for the
+/// whole stretch of inactivity and only the difference from what was already
+/// charged is recorded, so the pass can run at any frequency. Returns the
+/// number of users whose score decayed.
+pub async fn run_decay_pass(pool: &PgPool, now: DateTime<Utc>) -> Result<usize, sqlx::Error> {
+    let policy = trust::active_policy(&mut *pool.acquire().await?).await?;
+    let w = &policy.weights;
+    if w.decay_half_life_days <= 0 {
+        return Ok(0);
+    }
+
+    let candidates = fetch_candidates(&mut *pool.acquire().await?, now, w.decay_grace_days, None).await?;
+
+    let mut decayed = 0;
+    for user_id in candidates.into_iter().map(|c| c.user_id) {
+        let mut tx = pool.begin().await?;
+
+        // Another pass may have charged this user since the scan, so lock
+        // them and read their streak again before charging the difference.
+        sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        let Some(c) = fetch_candidates(&mut tx, now, w.decay_grace_days, Some(user_id)).await?.pop() else {
+            continue;
+        };
+
+        let days_inactive = (now - c.last_engaged_at).num_seconds() as f64 / 86_400.0;
+        let undecayed = c.score - c.streak_decay as i32;
+        let target = decay_target(undecayed, w.base_score, days_inactive, w.decay_grace_days, w.decay_half_life_days);
+        let delta = target - c.streak_decay as i32;
+
+        if delta >= 0 {
+            continue;
+        }
+
+        let description = format!("No activity for {} days", days_inactive.floor());
+        let event = NewTrustEvent {
+            event_type: TrustEventType::Decay,
+            quantity: delta,
+            source_type: "decay",
+            source_id: None,
+            description: &description,
+        };
+
+        trust::record_event(&mut tx, c.user_id, event).await?;
+        tx.commit().await?;
+        decayed += 1;
+    }
+
+    Ok(decayed)
+}
+
+/// Users who haven't engaged for longer than `grace_days`, or just `only` if
+/// given.
+async fn fetch_candidates(
+    conn: &mut PgConnection,
+    now: DateTime<Utc>,
+    grace_days: i32,
+    only: Option<Uuid>,
+) -> Result<Vec<DecayCandidate>, sqlx::Error> {
+    // Penalties and repricing are recorded by the server, so they don't count
+    // as the user engaging.
+    sqlx::query_as::<_, DecayCandidate>(
+        "WITH engagement AS (
+             SELECT u.id AS user_id,
+                    COALESCE(MAX(e.created_at) FILTER (
+                        WHERE e.event_type NOT IN ('decay', 'repricing', 'request_stalled', 'request_critical')
+                    ), u.created_at) AS last_engaged_at
+             FROM users u
+             LEFT JOIN trust_events e ON e.user_id = u.id
+             WHERE $3::uuid IS NULL OR u.id = $3
+             GROUP BY u.id, u.created_at
+         )
+         SELECT g.user_id, ts.score, g.last_engaged_at,
+                COALESCE((
+                    SELECT SUM(d.quantity) FROM trust_events d
+                    WHERE d.user_id = g.user_id AND d.event_type = 'decay' AND d.created_at > g.last_engaged_at
+                ), 0) AS streak_decay
+         FROM engagement g
+         JOIN trust_scores ts ON ts.user_id = g.user_id
+         WHERE g.last_engaged_at < $1 - make_interval(days => $2)"
+    )
+    .bind(now)
+    .bind(grace_days)
+    .bind(only)
+    .fetch_all(conn)
+    .await
+}
+
+/// Runs the decay pass once per `DECAY_INTERVAL` for the lifetime of the
+/// server.
+pub fn spawn_decay_job(pool: PgPool) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(DECAY_INTERVAL);
+        loop {
+            interval.tick().await;
+            match run_decay_pass(&pool, Utc::now()).await {
+                Ok(0) => {}
+                Ok(n) => println!("Trust decay: {} user(s) decayed", n),
+                Err(e) => eprintln!("Trust decay pass failed: {}", e),
+            }
+        }
+    });
+}
+
+pub async fn run_decay_now(
+    pool: web::Data<PgPool>,
+    _admin: AdminUser,
+) -> Result<HttpResponse, AppError> {
+    let decayed = run_decay_pass(pool.get_ref(), Utc::now()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"decayed": decayed}))))
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::models::{ApiResponse, RequestStatus};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+/// Every failure a handler can return. Each variant maps to an HTTP status
+/// and a stable `code` in the `ApiResponse` body so clients can branch on it
+/// without parsing the message.
+#[derive(Debug)]
+pub enum AppError {
+    Validation(Vec<FieldError>),
+    Unauthorized,
+    InvalidCredentials,
+    Forbidden,
+    NotFound(&'static str),
+    Conflict(String),
+    RateLimited { retry_after_secs: i64 },
+    InvalidStatusTransition { from: RequestStatus, to: RequestStatus },
+    Internal(String),
+}
+
+impl AppError {
+    pub fn invalid(field: &'static str, message: impl Into<String>) -> Self {
+        AppError::Validation(vec![FieldError::new(field, message)])
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Validation(_) => "VALIDATION_FAILED",
+            AppError::Unauthorized => "UNAUTHORIZED",
+            AppError::InvalidCredentials => "INVALID_CREDENTIALS",
+            AppError::Forbidden => "FORBIDDEN",
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Conflict(_) => "CONFLICT",
+            AppError::RateLimited { .. } => "RATE_LIMITED",
+            AppError::InvalidStatusTransition { .. } => "INVALID_STATUS_TRANSITION",
+            AppError::Internal(_) => "INTERNAL_ERROR",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Validation(_) => write!(f, "Validation failed"),
+            AppError::Unauthorized => write!(f, "Not authenticated"),
+            AppError::InvalidCredentials => write!(f, "Invalid email or password"),
+            AppError::Forbidden => write!(f, "Insufficient permissions"),
+            AppError::NotFound(what) => write!(f, "{} not found", what),
+            AppError::Conflict(msg) => write!(f, "{}", msg),
+            AppError::RateLimited { .. } => write!(f, "Too many requests, try again later"),
+            AppError::InvalidStatusTransition { from, to } => {
+                write!(f, "A {} request can't be moved to {}", from, to)
+            }
+            AppError::Internal(_) => write!(f, "Internal server error"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+impl From<bcrypt::BcryptError> for AppError {
+    fn from(e: bcrypt::BcryptError) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized | AppError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) | AppError::InvalidStatusTransition { .. } => StatusCode::CONFLICT,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = ApiResponse::<()>::err(&self.to_string()).with_code(self.code());
+
+        match self {
+            AppError::Validation(fields) => body.details = Some(fields.clone()),
+            AppError::Internal(cause) => {
+                // The cause can contain SQL or other internals, so it only
+                // goes to the log. The client gets an id to quote instead.
+                let correlation_id = Uuid::new_v4();
+                eprintln!("[{}] internal error: {}", correlation_id, cause);
+                body.correlation_id = Some(correlation_id);
+            }
+            _ => {}
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+        if let AppError::RateLimited { retry_after_secs } = self {
+            response.insert_header(("Retry-After", retry_after_secs.to_string()));
+        }
+        response.json(body)
+    }
+}
@@ -0,0 +1,338 @@
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::auth::{AdminUser, AuthenticatedUser};
+use crate::error::{AppError, FieldError};
+use crate::models::*;
+use crate::requests::record_status_change;
+use crate::trust;
+
+pub const DEFAULT_STALLED_AFTER_DAYS: i32 = 3;
+pub const DEFAULT_CRITICAL_AFTER_DAYS: i32 = 5;
+
+const ESCALATION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, FromRow)]
+struct EscalationCandidate {
+    id: Uuid,
+    user_id: Uuid,
+    title: String,
+    status: RequestStatus,
+    stalled_days: i32,
+    updated_at: DateTime<Utc>,
+    stalled_after_days: i32,
+    critical_after_days: i32,
+}
+
+pub fn days_since(updated_at: DateTime<Utc>, now: DateTime<Utc>) -> i32 {
+    (now - updated_at).num_days().max(0) as i32
+}
+
+/// Returns the status a request should move to after `stalled_days` without
+/// activity, or `None` if it stays where it is. Requests are only ever
+/// escalated (fair -> stalled -> critical), never relaxed.
+pub fn escalated_status(
+    status: RequestStatus,
+    stalled_days: i32,
+    stalled_after_days: i32,
+    critical_after_days: i32,
+) -> Option<RequestStatus> {
+    let rank = |s: RequestStatus| match s {
+        RequestStatus::Fair => Some(0),
+        RequestStatus::Stalled => Some(1),
+        RequestStatus::Critical => Some(2),
+        RequestStatus::Completed => None,
+    };
+
+    let current = rank(status)?;
+    let target = if stalled_days >= critical_after_days {
+        RequestStatus::Critical
+    } else if stalled_days >= stalled_after_days {
+        RequestStatus::Stalled
+    } else {
+        return None;
+    };
+
+    if rank(target)? > current {
+        Some(target)
+    } else {
+        None
+    }
+}
+
+/// Recomputes `stalled_days` for every open request relative to `now` and
+/// escalates the ones that crossed their owner's thresholds. Returns the
+/// number of requests whose status changed.
+pub async fn run_escalation_pass(pool: &PgPool, now: DateTime<Utc>) -> Result<usize, sqlx::Error> {
+    let candidates = sqlx::query_as::<_, EscalationCandidate>(
+        "SELECT r.id, r.user_id, r.title, r.status, r.stalled_days, r.updated_at,
+                COALESCE(s.stalled_after_days, $1) AS stalled_after_days,
+                COALESCE(s.critical_after_days, $2) AS critical_after_days
+         FROM requests r
+         LEFT JOIN escalation_settings s ON s.user_id = r.user_id
+         WHERE r.status <> 'completed' AND r.archived_at IS NULL AND r.deleted_at IS NULL"
+    )
+    .bind(DEFAULT_STALLED_AFTER_DAYS)
+    .bind(DEFAULT_CRITICAL_AFTER_DAYS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut escalated = 0;
+    for c in candidates {
+        let stalled_days = days_since(c.updated_at, now);
+        let new_status = escalated_status(c.status, stalled_days, c.stalled_after_days, c.critical_after_days);
+
+        if new_status.is_none() && stalled_days == c.stalled_days {
+            continue;
+        }
+
+        let status = new_status.unwrap_or(c.status);
+        let mut tx = pool.begin().await?;
+
+        let request = sqlx::query_as::<_, Request>(
+            "UPDATE requests SET status = $1, stalled_days = $2 WHERE id = $3 RETURNING *"
+        )
+        .bind(status)
+        .bind(stalled_days)
+        .bind(c.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if let Some(new_status) = new_status {
+            sqlx::query(
+                "INSERT INTO alerts (id, user_id, title, message, alert_type, request_id, created_at)
+                 VALUES ($1, $2, $3, $4, 'request', $5, $6)"
+            )
+            .bind(Uuid::new_v4())
+            .bind(c.user_id)
+            .bind(format!("Request {}", new_status.label()))
+            .bind(format!("{} has been stalled for {} days and is now {}.", c.title, stalled_days, new_status))
+            .bind(c.id)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+            let reason = format!("No activity for {} days", stalled_days);
+            record_status_change(&mut tx, &request, Some(c.status), new_status, None, Some(&reason), now).await?;
+            trust::rebuild_trust_score(&mut tx, c.user_id).await?;
+            escalated += 1;
+        }
+
+        tx.commit().await?;
+    }
+
+    Ok(escalated)
+}
+
+/// Runs the escalation pass once per `ESCALATION_INTERVAL` for the lifetime of
+/// the server.
+pub fn spawn_scheduler(pool: PgPool) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(ESCALATION_INTERVAL);
+        loop {
+            interval.tick().await;
+            match run_escalation_pass(&pool, Utc::now()).await {
+                Ok(0) => {}
+                Ok(n) => println!("Escalation pass: {} request(s) escalated", n),
+                Err(e) => eprintln!("Escalation pass failed: {}", e),
+            }
+        }
+    });
+}
+
+pub async fn get_escalation_settings(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+
+    let settings = sqlx::query_as::<_, EscalationSettings>(
+        "SELECT stalled_after_days, critical_after_days FROM escalation_settings WHERE user_id = $1"
+    )
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await?
+    .unwrap_or_default();
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(settings)))
+}
+
+pub async fn update_escalation_settings(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    body: web::Json<EscalationSettings>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+
+    let mut errors = Vec::new();
+    if body.stalled_after_days < 1 {
+        errors.push(FieldError::new("stalled_after_days", "Must be at least 1"));
+    }
+    if body.critical_after_days <= body.stalled_after_days {
+        errors.push(FieldError::new("critical_after_days", "Must be greater than stalled_after_days"));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let settings = sqlx::query_as::<_, EscalationSettings>(
+        "INSERT INTO escalation_settings (user_id, stalled_after_days, critical_after_days) VALUES ($1, $2, $3)
+         ON CONFLICT (user_id) DO UPDATE SET stalled_after_days = $2, critical_after_days = $3, updated_at = NOW()
+         RETURNING stalled_after_days, critical_after_days"
+    )
+    .bind(user_id)
+    .bind(body.stalled_after_days)
+    .bind(body.critical_after_days)
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(settings)))
+}
+
+pub async fn run_escalation_now(
+    pool: web::Data<PgPool>,
+    _admin: AdminUser,
+) -> Result<HttpResponse, AppError> {
+    let escalated = run_escalation_pass(pool.get_ref(), Utc::now()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"escalated": escalated}))))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::db;
+
+    fn at(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2030, 1, day, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn days_since_counts_whole_days() {
+        assert_eq!(days_since(at(1), at(1)), 0);
+        assert_eq!(days_since(at(1), at(2) - Duration::seconds(1)), 0);
+        assert_eq!(days_since(at(1), at(2)), 1);
+        assert_eq!(days_since(at(1), at(8)), 7);
+    }
+
+    #[test]
+    fn days_since_never_goes_negative() {
+        assert_eq!(days_since(at(5), at(1)), 0);
+    }
+
+    #[test]
+    fn escalated_status_follows_thresholds() {
+        assert_eq!(escalated_status(RequestStatus::Fair, 2, 3, 5), None);
+        assert_eq!(escalated_status(RequestStatus::Fair, 3, 3, 5), Some(RequestStatus::Stalled));
+        assert_eq!(escalated_status(RequestStatus::Fair, 5, 3, 5), Some(RequestStatus::Critical));
+        assert_eq!(escalated_status(RequestStatus::Stalled, 4, 3, 5), None);
+        assert_eq!(escalated_status(RequestStatus::Stalled, 6, 3, 5), Some(RequestStatus::Critical));
+    }
+
+    #[test]
+    fn escalated_status_never_relaxes_or_reopens() {
+        assert_eq!(escalated_status(RequestStatus::Critical, 3, 3, 5), None);
+        assert_eq!(escalated_status(RequestStatus::Critical, 0, 3, 5), None);
+        assert_eq!(escalated_status(RequestStatus::Completed, 30, 3, 5), None);
+    }
+
+    async fn insert_request(pool: &PgPool, user_id: Uuid, updated_at: DateTime<Utc>) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO requests (id, user_id, title, description, status, created_at, updated_at)
+             VALUES ($1, $2, 'Escalation test', '', 'fair', $3, $3)"
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(updated_at)
+        .execute(pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    async fn penalties(pool: &PgPool, request_id: Uuid) -> Vec<(TrustEventType, i32)> {
+        sqlx::query_as(
+            "SELECT event_type, quantity FROM trust_events WHERE source_id = $1 ORDER BY event_type"
+        )
+        .bind(request_id)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn escalation_pass_runs_against_a_fixed_clock() {
+        let Some(pool) = db::test_pool().await else {
+            eprintln!("TEST_DATABASE_URL not set; skipping");
+            return;
+        };
+        let user_id = db::create_test_user(&pool).await;
+        // Idle long enough to skip straight past stalled on the first pass.
+        let jumped = insert_request(&pool, user_id, at(1)).await;
+        // Stalled on the first pass, critical on the second.
+        let stepped = insert_request(&pool, user_id, at(3)).await;
+
+        let first = at(7);
+        run_escalation_pass(&pool, first).await.unwrap();
+
+        let (status, stalled_days): (RequestStatus, i32) =
+            sqlx::query_as("SELECT status, stalled_days FROM requests WHERE id = $1")
+                .bind(jumped)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(status, RequestStatus::Critical);
+        assert_eq!(stalled_days, 6);
+
+        let (status,): (RequestStatus,) = sqlx::query_as("SELECT status FROM requests WHERE id = $1")
+            .bind(stepped)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(status, RequestStatus::Stalled);
+
+        let (changed_at,): (DateTime<Utc>,) =
+            sqlx::query_as("SELECT changed_at FROM request_status_history WHERE request_id = $1")
+                .bind(jumped)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(changed_at, first);
+
+        let alert_times: Vec<(DateTime<Utc>,)> =
+            sqlx::query_as("SELECT created_at FROM alerts WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(alert_times.len(), 2);
+        assert!(alert_times.iter().all(|(t,)| *t == first));
+
+        let second = at(9);
+        run_escalation_pass(&pool, second).await.unwrap();
+
+        let (status, stalled_days): (RequestStatus, i32) =
+            sqlx::query_as("SELECT status, stalled_days FROM requests WHERE id = $1")
+                .bind(stepped)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(status, RequestStatus::Critical);
+        assert_eq!(stalled_days, 6);
+
+        // However often the scheduler looked, both requests pay the same.
+        let expected = vec![(TrustEventType::RequestStalled, 1), (TrustEventType::RequestCritical, 1)];
+        assert_eq!(penalties(&pool, jumped).await, expected);
+        assert_eq!(penalties(&pool, stepped).await, expected);
+
+        // A pass at the same instant changes nothing.
+        run_escalation_pass(&pool, second).await.unwrap();
+        assert_eq!(penalties(&pool, stepped).await, expected);
+    }
+}
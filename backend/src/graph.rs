@@ -0,0 +1,417 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::AppError;
+use crate::models::*;
+
+#[derive(Debug, FromRow)]
+struct PeerEdgeRow {
+    source: Uuid,
+    target: Uuid,
+    shared_requests: i32,
+    linked_interactions: i32,
+    last_interaction: Option<DateTime<Utc>>,
+}
+
+/// Builds `user_id`'s network graph: a direct edge to every peer, plus an
+/// edge between any two peers who are tagged on the same one of the user's
+/// requests or whose linked accounts have linked each other. Only accepted
+/// links count, in both directions, so a pending request reveals nothing.
+pub async fn load_graph(conn: &mut PgConnection, user_id: Uuid) -> Result<NetworkGraph, sqlx::Error> {
+    let (username,): (String,) = sqlx::query_as("SELECT username FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let peers = sqlx::query_as::<_, NetworkPeer>(
+        "SELECT np.*, pts.tier
+         FROM network_peers np
+         LEFT JOIN trust_scores pts ON pts.user_id = np.peer_user_id
+         WHERE np.user_id = $1
+         ORDER BY np.peer_name"
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let peer_edges = sqlx::query_as::<_, PeerEdgeRow>(
+        "WITH pairs AS (
+             SELECT a.id AS p, b.id AS q,
+                    COUNT(DISTINCT r.id) AS shared, 0 AS linked, MAX(r.updated_at) AS last_at
+             FROM requests r
+             JOIN request_peers ra ON ra.request_id = r.id
+             JOIN request_peers rb ON rb.request_id = r.id AND ra.peer_name < rb.peer_name
+             JOIN network_peers a ON a.user_id = r.user_id AND a.peer_name = ra.peer_name
+             JOIN network_peers b ON b.user_id = r.user_id AND b.peer_name = rb.peer_name
+             WHERE r.user_id = $1 AND r.deleted_at IS NULL
+             GROUP BY a.id, b.id
+             UNION ALL
+             SELECT a.id, b.id, 0, x.interactions + y.interactions, GREATEST(x.last_interaction, y.last_interaction)
+             FROM network_peers a
+             JOIN network_peers b ON b.user_id = a.user_id AND a.id < b.id
+             JOIN network_peers x ON x.user_id = a.peer_user_id AND x.peer_user_id = b.peer_user_id
+             JOIN network_peers y ON y.user_id = b.peer_user_id AND y.peer_user_id = a.peer_user_id
+             WHERE a.user_id = $1
+         )
+         SELECT LEAST(p, q) AS source, GREATEST(p, q) AS target,
+                SUM(shared)::int AS shared_requests,
+                SUM(linked)::int AS linked_interactions,
+                MAX(last_at) AS last_interaction
+         FROM pairs
+         GROUP BY 1, 2
+         ORDER BY 1, 2"
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut nodes = Vec::with_capacity(peers.len() + 1);
+    nodes.push(GraphNode {
+        id: user_id,
+        label: username,
+        kind: GraphNodeKind::User,
+        trust_level: None,
+        relationship_score: None,
+        tier: None,
+        x: 0.0,
+        y: 0.0,
+    });
+
+    let mut edges = Vec::with_capacity(peers.len() + peer_edges.len());
+    for peer in peers {
+        edges.push(GraphEdge {
+            source: user_id,
+            target: peer.id,
+            kind: GraphEdgeKind::Direct,
+            weight: peer.interactions,
+            shared_requests: 0,
+            linked_interactions: 0,
+            last_interaction: Some(peer.last_interaction),
+        });
+        nodes.push(GraphNode {
+            id: peer.id,
+            label: peer.peer_name,
+            kind: GraphNodeKind::Peer,
+            trust_level: Some(peer.trust_level),
+            relationship_score: peer.relationship_score,
+            tier: peer.tier,
+            x: peer.position_x,
+            y: peer.position_y,
+        });
+    }
+
+    edges.extend(peer_edges.into_iter().map(|e| GraphEdge {
+        source: e.source,
+        target: e.target,
+        kind: GraphEdgeKind::Peer,
+        weight: (e.shared_requests + e.linked_interactions).max(1),
+        shared_requests: e.shared_requests,
+        linked_interactions: e.linked_interactions,
+        last_interaction: e.last_interaction,
+    }));
+
+    Ok(NetworkGraph { nodes, edges })
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn dot_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn to_graphml(graph: &NetworkGraph) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n  \
+         <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n  \
+         <key id=\"kind\" for=\"all\" attr.name=\"kind\" attr.type=\"string\"/>\n  \
+         <key id=\"trust_level\" for=\"node\" attr.name=\"trust_level\" attr.type=\"string\"/>\n  \
+         <key id=\"relationship_score\" for=\"node\" attr.name=\"relationship_score\" attr.type=\"int\"/>\n  \
+         <key id=\"tier\" for=\"node\" attr.name=\"tier\" attr.type=\"string\"/>\n  \
+         <key id=\"x\" for=\"node\" attr.name=\"x\" attr.type=\"double\"/>\n  \
+         <key id=\"y\" for=\"node\" attr.name=\"y\" attr.type=\"double\"/>\n  \
+         <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n  \
+         <key id=\"shared_requests\" for=\"edge\" attr.name=\"shared_requests\" attr.type=\"int\"/>\n  \
+         <key id=\"linked_interactions\" for=\"edge\" attr.name=\"linked_interactions\" attr.type=\"int\"/>\n  \
+         <key id=\"last_interaction\" for=\"edge\" attr.name=\"last_interaction\" attr.type=\"string\"/>\n  \
+         <graph id=\"network\" edgedefault=\"undirected\">\n",
+    );
+
+    for node in &graph.nodes {
+        out.push_str(&format!("    <node id=\"{}\">\n", node.id));
+        out.push_str(&format!("      <data key=\"label\">{}</data>\n", xml_escape(&node.label)));
+        out.push_str(&format!("      <data key=\"kind\">{}</data>\n", node.kind.as_str()));
+        if let Some(level) = &node.trust_level {
+            out.push_str(&format!("      <data key=\"trust_level\">{}</data>\n", xml_escape(level)));
+        }
+        if let Some(score) = node.relationship_score {
+            out.push_str(&format!("      <data key=\"relationship_score\">{}</data>\n", score));
+        }
+        if let Some(tier) = node.tier {
+            out.push_str(&format!("      <data key=\"tier\">{}</data>\n", tier.label()));
+        }
+        out.push_str(&format!("      <data key=\"x\">{}</data>\n", node.x));
+        out.push_str(&format!("      <data key=\"y\">{}</data>\n", node.y));
+        out.push_str("    </node>\n");
+    }
+
+    for edge in &graph.edges {
+        out.push_str(&format!("    <edge source=\"{}\" target=\"{}\">\n", edge.source, edge.target));
+        out.push_str(&format!("      <data key=\"kind\">{}</data>\n", edge.kind.as_str()));
+        out.push_str(&format!("      <data key=\"weight\">{}</data>\n", edge.weight));
+        out.push_str(&format!("      <data key=\"shared_requests\">{}</data>\n", edge.shared_requests));
+        out.push_str(&format!("      <data key=\"linked_interactions\">{}</data>\n", edge.linked_interactions));
+        if let Some(at) = edge.last_interaction {
+            out.push_str(&format!("      <data key=\"last_interaction\">{}</data>\n", at.to_rfc3339()));
+        }
+        out.push_str("    </edge>\n");
+    }
+
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+fn to_gexf(graph: &NetworkGraph) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <gexf xmlns=\"http://gexf.net/1.3\" xmlns:viz=\"http://gexf.net/1.3/viz\" version=\"1.3\">\n  \
+         <graph defaultedgetype=\"undirected\" mode=\"static\">\n    \
+         <attributes class=\"node\">\n      \
+         <attribute id=\"kind\" title=\"kind\" type=\"string\"/>\n      \
+         <attribute id=\"trust_level\" title=\"trust_level\" type=\"string\"/>\n      \
+         <attribute id=\"relationship_score\" title=\"relationship_score\" type=\"integer\"/>\n      \
+         <attribute id=\"tier\" title=\"tier\" type=\"string\"/>\n    \
+         </attributes>\n    \
+         <attributes class=\"edge\">\n      \
+         <attribute id=\"kind\" title=\"kind\" type=\"string\"/>\n      \
+         <attribute id=\"shared_requests\" title=\"shared_requests\" type=\"integer\"/>\n      \
+         <attribute id=\"linked_interactions\" title=\"linked_interactions\" type=\"integer\"/>\n      \
+         <attribute id=\"last_interaction\" title=\"last_interaction\" type=\"string\"/>\n    \
+         </attributes>\n    \
+         <nodes>\n",
+    );
+
+    for node in &graph.nodes {
+        out.push_str(&format!("      <node id=\"{}\" label=\"{}\">\n", node.id, xml_escape(&node.label)));
+        out.push_str("        <attvalues>\n");
+        out.push_str(&format!("          <attvalue for=\"kind\" value=\"{}\"/>\n", node.kind.as_str()));
+        if let Some(level) = &node.trust_level {
+            out.push_str(&format!("          <attvalue for=\"trust_level\" value=\"{}\"/>\n", xml_escape(level)));
+        }
+        if let Some(score) = node.relationship_score {
+            out.push_str(&format!("          <attvalue for=\"relationship_score\" value=\"{}\"/>\n", score));
+        }
+        if let Some(tier) = node.tier {
+            out.push_str(&format!("          <attvalue for=\"tier\" value=\"{}\"/>\n", tier.label()));
+        }
+        out.push_str("        </attvalues>\n");
+        out.push_str(&format!("        <viz:position x=\"{}\" y=\"{}\" z=\"0\"/>\n", node.x, node.y));
+        out.push_str("      </node>\n");
+    }
+
+    out.push_str("    </nodes>\n    <edges>\n");
+    for (i, edge) in graph.edges.iter().enumerate() {
+        out.push_str(&format!(
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\">\n",
+            i, edge.source, edge.target, edge.weight
+        ));
+        out.push_str("        <attvalues>\n");
+        out.push_str(&format!("          <attvalue for=\"kind\" value=\"{}\"/>\n", edge.kind.as_str()));
+        out.push_str(&format!("          <attvalue for=\"shared_requests\" value=\"{}\"/>\n", edge.shared_requests));
+        out.push_str(&format!(
+            "          <attvalue for=\"linked_interactions\" value=\"{}\"/>\n",
+            edge.linked_interactions
+        ));
+        if let Some(at) = edge.last_interaction {
+            out.push_str(&format!("          <attvalue for=\"last_interaction\" value=\"{}\"/>\n", at.to_rfc3339()));
+        }
+        out.push_str("        </attvalues>\n");
+        out.push_str("      </edge>\n");
+    }
+
+    out.push_str("    </edges>\n  </graph>\n</gexf>\n");
+    out
+}
+
+fn to_dot(graph: &NetworkGraph) -> String {
+    let mut out = String::from("graph network {\n");
+
+    for node in &graph.nodes {
+        let mut attrs = vec![
+            format!("label=\"{}\"", dot_escape(&node.label)),
+            format!("kind=\"{}\"", node.kind.as_str()),
+            format!("pos=\"{},{}!\"", node.x, node.y),
+        ];
+        if let Some(level) = &node.trust_level {
+            attrs.push(format!("trust_level=\"{}\"", dot_escape(level)));
+        }
+        if let Some(score) = node.relationship_score {
+            attrs.push(format!("relationship_score={}", score));
+        }
+        if let Some(tier) = node.tier {
+            attrs.push(format!("tier=\"{}\"", tier.label()));
+        }
+        out.push_str(&format!("  \"{}\" [{}];\n", node.id, attrs.join(", ")));
+    }
+
+    for edge in &graph.edges {
+        let mut attrs = vec![
+            format!("kind=\"{}\"", edge.kind.as_str()),
+            format!("weight={}", edge.weight),
+            format!("shared_requests={}", edge.shared_requests),
+            format!("linked_interactions={}", edge.linked_interactions),
+        ];
+        if let Some(at) = edge.last_interaction {
+            attrs.push(format!("last_interaction=\"{}\"", at.to_rfc3339()));
+        }
+        out.push_str(&format!("  \"{}\" -- \"{}\" [{}];\n", edge.source, edge.target, attrs.join(", ")));
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn export(body: String, content_type: &str, extension: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"network.{}\"", extension)))
+        .body(body)
+}
+
+pub async fn get_network_graph(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<GraphQuery>,
+) -> Result<HttpResponse, AppError> {
+    let graph = load_graph(&mut *pool.acquire().await?, user.id).await?;
+
+    Ok(match query.format.unwrap_or_default() {
+        GraphFormat::Json => HttpResponse::Ok().json(ApiResponse::ok(graph)),
+        GraphFormat::Graphml => export(to_graphml(&graph), "application/graphml+xml", "graphml"),
+        GraphFormat::Gexf => export(to_gexf(&graph), "application/gexf+xml", "gexf"),
+        GraphFormat::Dot => export(to_dot(&graph), "text/vnd.graphviz", "dot"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    const USER: Uuid = Uuid::from_u128(1);
+    const ADA: Uuid = Uuid::from_u128(2);
+    const GRACE: Uuid = Uuid::from_u128(3);
+
+    fn node(id: Uuid, label: &str, kind: GraphNodeKind) -> GraphNode {
+        let peer = kind == GraphNodeKind::Peer;
+        GraphNode {
+            id,
+            label: label.to_string(),
+            kind,
+            trust_level: peer.then(|| "High".to_string()),
+            relationship_score: peer.then_some(72),
+            tier: peer.then_some(TrustTier::Trusted),
+            x: if peer { 0.5 } else { 0.0 },
+            y: if peer { -0.25 } else { 0.0 },
+        }
+    }
+
+    fn edge(source: Uuid, target: Uuid, kind: GraphEdgeKind, last_interaction: bool) -> GraphEdge {
+        GraphEdge {
+            source,
+            target,
+            kind,
+            weight: 3,
+            shared_requests: 2,
+            linked_interactions: 1,
+            last_interaction: last_interaction.then(|| Utc.with_ymd_and_hms(2030, 1, 2, 3, 4, 5).unwrap()),
+        }
+    }
+
+    /// The user, a peer whose name needs escaping in every format, and a
+    /// second peer sharing a request with the first.
+    fn graph() -> NetworkGraph {
+        NetworkGraph {
+            nodes: vec![
+                node(USER, "Me", GraphNodeKind::User),
+                node(ADA, r#"Ada "A&B" <Lovelace> \ O'Neil"#, GraphNodeKind::Peer),
+                node(GRACE, "Grace", GraphNodeKind::Peer),
+            ],
+            edges: vec![
+                edge(USER, ADA, GraphEdgeKind::Direct, true),
+                edge(USER, GRACE, GraphEdgeKind::Direct, false),
+                edge(ADA, GRACE, GraphEdgeKind::Peer, false),
+            ],
+        }
+    }
+
+    #[test]
+    fn xml_escape_escapes_markup_once() {
+        assert_eq!(
+            xml_escape(r#"<a href="x">Tom & 'Jerry'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;"
+        );
+        assert_eq!(xml_escape("&amp;"), "&amp;amp;");
+        assert_eq!(xml_escape("plain"), "plain");
+    }
+
+    #[test]
+    fn dot_escape_escapes_quotes_and_backslashes() {
+        assert_eq!(dot_escape(r#"say "hi" \ bye"#), r#"say \"hi\" \\ bye"#);
+        assert_eq!(dot_escape(r#"\""#), r#"\\\""#);
+    }
+
+    #[test]
+    fn graphml_lists_every_node_and_edge() {
+        let out = to_graphml(&graph());
+
+        assert!(out.starts_with("<?xml"));
+        assert!(out.ends_with("</graphml>\n"));
+        assert_eq!(out.matches("<node ").count(), 3);
+        assert_eq!(out.matches("<edge ").count(), 3);
+        assert!(out.contains("<data key=\"label\">Ada &quot;A&amp;B&quot; &lt;Lovelace&gt; \\ O&apos;Neil</data>"));
+        assert!(out.contains(&format!("<edge source=\"{}\" target=\"{}\">", ADA, GRACE)));
+        assert_eq!(out.matches("<data key=\"last_interaction\">2030-01-02T03:04:05+00:00</data>").count(), 1);
+        // The user has no trust level, score or tier.
+        assert_eq!(out.matches("<data key=\"tier\">").count(), 2);
+    }
+
+    #[test]
+    fn gexf_lists_every_node_and_edge() {
+        let out = to_gexf(&graph());
+
+        assert!(out.ends_with("</gexf>\n"));
+        assert_eq!(out.matches("<node ").count(), 3);
+        assert_eq!(out.matches("<edge ").count(), 3);
+        assert!(out.contains(&format!(
+            "<node id=\"{}\" label=\"Ada &quot;A&amp;B&quot; &lt;Lovelace&gt; \\ O&apos;Neil\">",
+            ADA
+        )));
+        assert!(out.contains(&format!("<edge id=\"2\" source=\"{}\" target=\"{}\" weight=\"3\">", ADA, GRACE)));
+        assert!(out.contains("<viz:position x=\"0.5\" y=\"-0.25\" z=\"0\"/>"));
+        assert_eq!(out.matches("<attvalue for=\"relationship_score\" value=\"72\"/>").count(), 2);
+    }
+
+    #[test]
+    fn dot_lists_every_node_and_edge() {
+        let out = to_dot(&graph());
+
+        assert!(out.starts_with("graph network {\n"));
+        assert!(out.ends_with("}\n"));
+        assert_eq!(out.matches(" -- ").count(), 3);
+        assert!(out.contains(r#"label="Ada \"A&B\" <Lovelace> \\ O'Neil""#));
+        assert!(out.contains(&format!("\"{}\" -- \"{}\" [kind=\"peer\", weight=3", ADA, GRACE)));
+        assert!(out.contains("pos=\"0.5,-0.25!\""));
+    }
+}
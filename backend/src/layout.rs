@@ -0,0 +1,297 @@
+use actix_web::{web, HttpResponse};
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::AppError;
+use crate::models::*;
+
+// Positions are relative to the user, who sits at the origin; the map scales
+// the unit circle to its own size.
+const MIN_RADIUS: f64 = 0.2;
+const MAX_RADIUS: f64 = 0.9;
+
+const FULL_ITERATIONS: usize = 300;
+const FULL_TEMPERATURE: f64 = 0.1;
+/// Incremental runs start from the stored positions and move existing nodes
+/// only a little, so the map doesn't jump when one peer changes.
+const INCREMENTAL_ITERATIONS: usize = 60;
+const INCREMENTAL_TEMPERATURE: f64 = 0.02;
+
+/// How strongly a node is pulled back to its target distance from the user.
+const RADIAL_STRENGTH: f64 = 0.5;
+/// Interactions beyond this many don't bring a peer any closer.
+const INTERACTION_SCALE: f64 = 50.0;
+
+#[derive(Debug, FromRow)]
+struct LayoutRow {
+    id: Uuid,
+    trust_level: String,
+    interactions: i32,
+    position_x: f64,
+    position_y: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutNode {
+    pub id: Uuid,
+    pub x: f64,
+    pub y: f64,
+    /// Distance from the user the node settles at: close for strong
+    /// relationships, far for weak ones.
+    pub target_radius: f64,
+}
+
+impl LayoutNode {
+    /// Stored positions are never exactly at the origin once laid out, so
+    /// (0, 0) marks a peer that hasn't been placed yet.
+    fn is_placed(&self) -> bool {
+        self.x != 0.0 || self.y != 0.0
+    }
+}
+
+fn trust_weight(trust_level: &str) -> f64 {
+    match trust_level {
+        "High" => 1.0,
+        "Medium" => 0.6,
+        _ => 0.3,
+    }
+}
+
+/// Where a peer should sit, from 0 (the user) to 1 (the edge of the map).
+pub fn target_radius(trust_level: &str, interactions: i32) -> f64 {
+    let frequency = ((1.0 + interactions.max(0) as f64).ln() / (1.0 + INTERACTION_SCALE).ln()).min(1.0);
+    let closeness = 0.6 * trust_weight(trust_level) + 0.4 * frequency;
+    MIN_RADIUS + (MAX_RADIUS - MIN_RADIUS) * (1.0 - closeness)
+}
+
+/// SplitMix64, used so initial placement depends only on the seed and the
+/// peer's id.
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn initial_angle(seed: u64, id: Uuid) -> f64 {
+    let (hi, lo) = id.as_u64_pair();
+    let unit = (mix(seed ^ mix(hi ^ mix(lo))) >> 11) as f64 / (1u64 << 53) as f64;
+    unit * std::f64::consts::TAU
+}
+
+/// The seed a user's layout uses unless another is given.
+pub fn default_seed(user_id: Uuid) -> u64 {
+    let (hi, lo) = user_id.as_u64_pair();
+    hi ^ lo
+}
+
+/// Force-directed placement around the user at the origin. Each node is
+/// pulled towards its target radius and pushed away from the others; the
+/// step size cools linearly to zero. With `incremental`, placed nodes start
+/// where they are and the run is shorter and cooler. Nodes are processed in
+/// id order, so the result depends only on the inputs and `seed`.
+pub fn compute_layout(nodes: &mut [LayoutNode], seed: u64, incremental: bool) {
+    if nodes.is_empty() {
+        return;
+    }
+    nodes.sort_by_key(|n| n.id);
+
+    for node in nodes.iter_mut() {
+        if !incremental || !node.is_placed() {
+            let angle = initial_angle(seed, node.id);
+            node.x = node.target_radius * angle.cos();
+            node.y = node.target_radius * angle.sin();
+        }
+    }
+
+    let (iterations, temperature) = if incremental {
+        (INCREMENTAL_ITERATIONS, INCREMENTAL_TEMPERATURE)
+    } else {
+        (FULL_ITERATIONS, FULL_TEMPERATURE)
+    };
+    // Ideal spacing for this many nodes in the unit circle.
+    let k = (std::f64::consts::PI / nodes.len() as f64).sqrt() * 0.5;
+
+    for step in 0..iterations {
+        let limit = temperature * (1.0 - step as f64 / iterations as f64);
+        let mut displacement = vec![(0.0, 0.0); nodes.len()];
+
+        for i in 0..nodes.len() {
+            let a = nodes[i];
+            for j in i + 1..nodes.len() {
+                let b = nodes[j];
+                let (dx, dy) = (a.x - b.x, a.y - b.y);
+                let distance = (dx * dx + dy * dy).sqrt().max(1e-3);
+                let force = k * k / distance;
+                let (fx, fy) = (dx / distance * force, dy / distance * force);
+                displacement[i].0 += fx;
+                displacement[i].1 += fy;
+                displacement[j].0 -= fx;
+                displacement[j].1 -= fy;
+            }
+
+            let radius = (a.x * a.x + a.y * a.y).sqrt().max(1e-3);
+            let pull = (radius - a.target_radius) * RADIAL_STRENGTH * nodes.len() as f64;
+            displacement[i].0 -= a.x / radius * pull;
+            displacement[i].1 -= a.y / radius * pull;
+        }
+
+        for (node, (dx, dy)) in nodes.iter_mut().zip(displacement) {
+            let length = (dx * dx + dy * dy).sqrt();
+            if length > 0.0 {
+                let scale = length.min(limit) / length;
+                node.x += dx * scale;
+                node.y += dy * scale;
+            }
+
+            let radius = (node.x * node.x + node.y * node.y).sqrt();
+            let clamped = radius.clamp(MIN_RADIUS / 2.0, MAX_RADIUS);
+            if radius > 0.0 && clamped != radius {
+                node.x *= clamped / radius;
+                node.y *= clamped / radius;
+            }
+        }
+    }
+}
+
+/// Recomputes and stores the positions of `user_id`'s peers. Incremental
+/// runs keep placed peers close to where they were; use it after peers are
+/// added, removed or re-weighted.
+pub async fn refresh_layout(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    seed: u64,
+    incremental: bool,
+) -> Result<(), sqlx::Error> {
+    let rows = sqlx::query_as::<_, LayoutRow>(
+        "SELECT id, trust_level, interactions, position_x, position_y FROM network_peers WHERE user_id = $1"
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut nodes: Vec<LayoutNode> = rows
+        .into_iter()
+        .map(|r| LayoutNode {
+            id: r.id,
+            x: r.position_x,
+            y: r.position_y,
+            target_radius: target_radius(&r.trust_level, r.interactions),
+        })
+        .collect();
+    compute_layout(&mut nodes, seed, incremental);
+
+    let ids: Vec<Uuid> = nodes.iter().map(|n| n.id).collect();
+    let xs: Vec<f64> = nodes.iter().map(|n| n.x).collect();
+    let ys: Vec<f64> = nodes.iter().map(|n| n.y).collect();
+
+    sqlx::query(
+        "UPDATE network_peers np SET position_x = p.x, position_y = p.y
+         FROM UNNEST($1::uuid[], $2::float8[], $3::float8[]) AS p(id, x, y)
+         WHERE np.id = p.id"
+    )
+    .bind(&ids)
+    .bind(&xs)
+    .bind(&ys)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Lays the whole network out from scratch. The same seed and peers always
+/// give the same map.
+pub async fn relayout_network(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<LayoutQuery>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let seed = query.seed.unwrap_or_else(|| default_seed(user_id));
+
+    let mut tx = pool.begin().await?;
+    refresh_layout(&mut tx, user_id, seed, false).await?;
+
+    let peers = sqlx::query_as::<_, NetworkPeer>(
+        "SELECT np.*, pts.tier
+         FROM network_peers np
+         LEFT JOIN trust_scores pts ON pts.user_id = np.peer_user_id
+         WHERE np.user_id = $1
+         ORDER BY np.peer_name"
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(peers)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(count: u128) -> Vec<LayoutNode> {
+        let levels = ["High", "Medium", "Low"];
+        (0..count)
+            .map(|i| LayoutNode {
+                id: Uuid::from_u128(i + 1),
+                x: 0.0,
+                y: 0.0,
+                target_radius: target_radius(levels[i as usize % 3], (i * 7) as i32),
+            })
+            .collect()
+    }
+
+    fn positions(nodes: &[LayoutNode]) -> Vec<(f64, f64)> {
+        nodes.iter().map(|n| (n.x, n.y)).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_layout() {
+        let (mut a, mut b) = (nodes(12), nodes(12));
+        b.reverse();
+        compute_layout(&mut a, 42, false);
+        compute_layout(&mut b, 42, false);
+        assert_eq!(positions(&a), positions(&b));
+    }
+
+    #[test]
+    fn different_seed_gives_a_different_layout() {
+        let (mut a, mut b) = (nodes(12), nodes(12));
+        compute_layout(&mut a, 42, false);
+        compute_layout(&mut b, 43, false);
+        assert_ne!(positions(&a), positions(&b));
+    }
+
+    #[test]
+    fn incremental_runs_move_placed_nodes_a_bounded_distance() {
+        let mut laid_out = nodes(12);
+        compute_layout(&mut laid_out, 42, false);
+
+        let mut moved = laid_out.clone();
+        moved[0].target_radius = MAX_RADIUS;
+        moved.push(nodes(13)[12]);
+        compute_layout(&mut moved, 42, true);
+
+        let bound = INCREMENTAL_TEMPERATURE * INCREMENTAL_ITERATIONS as f64 + 1e-9;
+        for before in &laid_out {
+            let after = moved.iter().find(|n| n.id == before.id).unwrap();
+            let distance = ((after.x - before.x).powi(2) + (after.y - before.y).powi(2)).sqrt();
+            assert!(distance <= bound, "{} moved {}", before.id, distance);
+        }
+    }
+
+    #[test]
+    fn nodes_stay_within_the_map() {
+        for count in [1, 2, 12, 80] {
+            let mut layout = nodes(count);
+            compute_layout(&mut layout, 7, false);
+            for node in &layout {
+                assert!((node.x * node.x + node.y * node.y).sqrt() <= MAX_RADIUS + 1e-9);
+            }
+        }
+    }
+}
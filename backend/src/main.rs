@@ -1,9 +1,23 @@
 mod models;
 mod db;
+mod error;
 mod auth;
 mod requests;
 mod trust;
 mod alerts;
+mod agreements;
+mod escalation;
+mod pagination;
+mod activity;
+mod decay;
+mod relationships;
+mod waves;
+mod analytics;
+mod network;
+mod layout;
+mod graph;
+mod network_analytics;
+mod contacts;
 
 use actix_cors::Cors;
 use actix_web::{web, App, HttpServer, HttpResponse, middleware};
@@ -26,18 +40,63 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to connect to database");
 
-    println!("Connected to database. Creating tables...");
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    db::create_tables(&pool)
+    if args.iter().any(|a| a == "--rollback") {
+        match db::rollback_last_migration(&pool).await.expect("Failed to roll back migration") {
+            Some(version) => println!("Rolled back migration {}.", version),
+            None => println!("No migrations to roll back."),
+        }
+        return Ok(());
+    }
+
+    println!("Connected to database. Running migrations...");
+
+    db::run_migrations(&pool)
         .await
-        .expect("Failed to create tables");
+        .expect("Failed to run migrations");
+
+    if let Some(i) = args.iter().position(|a| a == "--grant-admin") {
+        let email = args.get(i + 1).expect("--grant-admin needs the user's email");
+        if db::grant_admin(&pool, email).await.expect("Failed to grant admin role") {
+            println!("Granted the admin role to {}.", email);
+        } else {
+            println!("No user with email {}.", email);
+        }
+        return Ok(());
+    }
 
-    println!("Tables created. Seeding demo data...");
+    if args.iter().any(|a| a == "--migrate-only") {
+        println!("Migrations complete.");
+        return Ok(());
+    }
+
+    println!("Migrations complete. Seeding demo data...");
 
     db::seed_demo_data(&pool)
         .await
         .expect("Failed to seed demo data");
 
+    let rehashed = contacts::refresh_identifier_hashes(&pool)
+        .await
+        .expect("Failed to refresh contact hashes");
+    if rehashed > 0 {
+        println!("Hashed contact identifiers for {} user(s)", rehashed);
+    }
+
+    let rebuilt = trust::rebuild_stale_scores(&pool)
+        .await
+        .expect("Failed to rebuild trust scores");
+    if rebuilt > 0 {
+        println!("Rebuilt {} trust score(s)", rebuilt);
+    }
+
+    escalation::spawn_scheduler(pool.clone());
+    auth::spawn_session_purge(pool.clone());
+    trust::spawn_snapshot_job(pool.clone());
+    decay::spawn_decay_job(pool.clone());
+    relationships::spawn_refresh_job(pool.clone());
+
     println!("Starting Trust OS backend on http://0.0.0.0:3001");
 
     HttpServer::new(move || {
@@ -47,19 +106,73 @@ async fn main() -> std::io::Result<()> {
             .wrap(cors)
             .wrap(middleware::Logger::default())
             .app_data(web::Data::new(pool.clone()))
+            .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                error::AppError::invalid("body", err.to_string()).into()
+            }))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                error::AppError::invalid("query", err.to_string()).into()
+            }))
+            .app_data(web::PathConfig::default().error_handler(|err, _| {
+                error::AppError::invalid("path", err.to_string()).into()
+            }))
             .route("/health", web::get().to(health))
             .route("/api/auth/register", web::post().to(auth::register))
             .route("/api/auth/login", web::post().to(auth::login))
             .route("/api/auth/me", web::get().to(auth::me))
+            .route("/api/auth/logout", web::post().to(auth::logout))
+            .route("/api/auth/logout-all", web::post().to(auth::logout_all))
+            .route("/api/auth/sessions", web::get().to(auth::list_sessions))
             .route("/api/requests", web::get().to(requests::list_requests))
             .route("/api/requests", web::post().to(requests::create_request))
             .route("/api/requests/{id}", web::get().to(requests::get_request))
+            .route("/api/requests/{id}", web::patch().to(requests::update_request))
+            .route("/api/requests/{id}", web::delete().to(requests::delete_request))
+            .route("/api/requests/{id}/restore", web::post().to(requests::restore_request))
+            .route("/api/requests/{id}/archive", web::post().to(requests::archive_request))
+            .route("/api/requests/{id}/unarchive", web::post().to(requests::unarchive_request))
+            .route("/api/requests/{id}/peers", web::post().to(requests::add_request_peers))
+            .route("/api/requests/{id}/peers", web::delete().to(requests::remove_request_peers))
             .route("/api/requests/{id}/status", web::put().to(requests::update_request_status))
+            .route("/api/requests/{id}/history", web::get().to(requests::get_request_history))
+            .route("/api/requests/{id}/agreements", web::get().to(agreements::list_request_agreements))
+            .route("/api/requests/{id}/agreements", web::post().to(agreements::propose_agreement))
+            .route("/api/agreements/{id}", web::get().to(agreements::get_agreement))
+            .route("/api/agreements/{id}/accept", web::post().to(agreements::accept_agreement))
+            .route("/api/agreements/{id}/counter", web::post().to(agreements::counter_agreement))
+            .route("/api/agreements/{id}/reject", web::post().to(agreements::reject_agreement))
             .route("/api/trust-score", web::get().to(trust::get_trust_score))
             .route("/api/trust-score/recalculate", web::post().to(trust::recalculate_trust_score))
+            .route("/api/trust-score/simulate", web::post().to(trust::simulate_trust_score))
+            .route("/api/trust-score/events", web::get().to(trust::list_trust_events))
+            .route("/api/trust-score/history", web::get().to(trust::get_trust_score_history))
+            .route("/api/trust-score/waves", web::get().to(waves::get_own_waves))
+            .route("/api/analytics/summary", web::get().to(analytics::get_summary))
             .route("/api/network", web::get().to(trust::list_network_peers))
+            .route("/api/network", web::post().to(network::create_network_peer))
+            .route("/api/network/waves", web::get().to(waves::get_wave_summary))
+            .route("/api/network/layout", web::post().to(layout::relayout_network))
+            .route("/api/network/graph", web::get().to(graph::get_network_graph))
+            .route("/api/network/analytics", web::get().to(network_analytics::get_network_analytics))
+            .route("/api/network/contacts/hashing", web::get().to(contacts::get_contact_hashing))
+            .route("/api/network/contacts/sync", web::post().to(contacts::sync_contacts))
+            .route("/api/network/link-requests", web::get().to(network::list_link_requests))
+            .route("/api/network/link-requests/{peer_id}", web::post().to(network::respond_to_link_request))
+            .route("/api/network/{peer_id}", web::get().to(relationships::get_network_peer))
+            .route("/api/network/{peer_id}", web::patch().to(network::update_network_peer))
+            .route("/api/network/{peer_id}", web::delete().to(network::delete_network_peer))
+            .route("/api/network/{peer_id}/waves", web::get().to(waves::get_peer_waves))
             .route("/api/alerts", web::get().to(alerts::list_alerts))
             .route("/api/alerts/{id}/read", web::put().to(alerts::mark_alert_read))
+            .route("/api/settings/escalation", web::get().to(escalation::get_escalation_settings))
+            .route("/api/settings/escalation", web::put().to(escalation::update_escalation_settings))
+            .route("/api/settings/timezone", web::put().to(auth::update_timezone))
+            .route("/api/settings/contact-discovery", web::put().to(auth::update_contact_discovery))
+            .route("/api/activity", web::post().to(activity::record_activity))
+            .route("/api/admin/escalation/run", web::post().to(escalation::run_escalation_now))
+            .route("/api/admin/decay/run", web::post().to(decay::run_decay_now))
+            .route("/api/admin/scoring-policies", web::get().to(trust::list_scoring_policies))
+            .route("/api/admin/scoring-policies", web::post().to(trust::create_scoring_policy))
+            .route("/api/admin/scoring-policies/preview", web::post().to(trust::preview_scoring_policy))
     })
     .bind("0.0.0.0:3001")?
     .run()
@@ -1,15 +1,21 @@
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::error::FieldError;
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
     pub email: String,
     pub password_hash: String,
+    pub role: String,
     pub created_at: DateTime<Utc>,
+    pub timezone: String,
+    pub phone: Option<String>,
+    pub contact_discovery: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,6 +23,13 @@ pub struct UserResponse {
     pub id: Uuid,
     pub username: String,
     pub email: String,
+    pub role: String,
+    pub timezone: String,
+    pub phone: Option<String>,
+    /// Whether other users can find this one by contact sync.
+    pub contact_discovery: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<TrustTier>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -26,6 +39,11 @@ impl From<User> for UserResponse {
             id: u.id,
             username: u.username,
             email: u.email,
+            role: u.role,
+            timezone: u.timezone,
+            phone: u.phone,
+            contact_discovery: u.contact_discovery,
+            tier: None,
             created_at: u.created_at,
         }
     }
@@ -50,15 +68,94 @@ pub struct AuthResponse {
     pub token: String,
 }
 
+#[derive(Debug, Serialize, FromRow)]
+pub struct SessionInfo {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub current: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "request_status", rename_all = "lowercase")]
+pub enum RequestStatus {
+    Fair,
+    Stalled,
+    Critical,
+    Completed,
+}
+
+impl RequestStatus {
+    /// The statuses a request may move to from `self`. Open requests can move
+    /// freely between fair, stalled and critical or be completed; completed
+    /// is terminal.
+    pub fn allowed_transitions(self) -> &'static [RequestStatus] {
+        use RequestStatus::*;
+        match self {
+            Fair => &[Stalled, Critical, Completed],
+            Stalled => &[Fair, Critical, Completed],
+            Critical => &[Fair, Stalled, Completed],
+            Completed => &[],
+        }
+    }
+
+    pub fn can_transition_to(self, next: RequestStatus) -> bool {
+        self.allowed_transitions().contains(&next)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RequestStatus::Fair => "fair",
+            RequestStatus::Stalled => "stalled",
+            RequestStatus::Critical => "critical",
+            RequestStatus::Completed => "completed",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RequestStatus::Fair => "Fair",
+            RequestStatus::Stalled => "Stalled",
+            RequestStatus::Critical => "Critical",
+            RequestStatus::Completed => "Completed",
+        }
+    }
+}
+
+impl std::str::FromStr for RequestStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fair" => Ok(RequestStatus::Fair),
+            "stalled" => Ok(RequestStatus::Stalled),
+            "critical" => Ok(RequestStatus::Critical),
+            "completed" => Ok(RequestStatus::Completed),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for RequestStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Request {
     pub id: Uuid,
     pub user_id: Uuid,
     pub title: String,
     pub description: String,
-    pub status: String,
+    pub status: RequestStatus,
     pub stalled_days: i32,
     pub document_id: Option<String>,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -67,14 +164,68 @@ pub struct Request {
 pub struct CreateRequestBody {
     pub title: String,
     pub description: String,
-    pub status: Option<String>,
+    pub status: Option<RequestStatus>,
     pub peers: Option<Vec<String>>,
     pub document_id: Option<String>,
 }
 
+/// Lets a PATCH body distinguish a field that was omitted (`None`) from one
+/// explicitly set to null (`Some(None)`).
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRequestBody {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub document_id: Option<Option<String>>,
+    pub peers: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RequestPeersBody {
+    pub peers: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RequestListQuery {
+    pub archived: Option<bool>,
+    /// Comma-separated list of statuses, e.g. `stalled,critical`.
+    pub status: Option<String>,
+    pub peer: Option<String>,
+    pub created_from: Option<DateTime<Utc>>,
+    pub created_to: Option<DateTime<Utc>>,
+    pub updated_from: Option<DateTime<Utc>>,
+    pub updated_to: Option<DateTime<Utc>>,
+    /// Full-text search over title and description.
+    pub q: Option<String>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateRequestStatus {
-    pub status: String,
+    pub status: RequestStatus,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct RequestStatusChange {
+    pub id: Uuid,
+    pub request_id: Uuid,
+    pub from_status: Option<RequestStatus>,
+    pub to_status: RequestStatus,
+    pub changed_by: Option<Uuid>,
+    pub reason: Option<String>,
+    pub changed_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -90,14 +241,91 @@ pub struct RequestWithPeers {
     pub user_id: Uuid,
     pub title: String,
     pub description: String,
-    pub status: String,
+    pub status: RequestStatus,
     pub stalled_days: i32,
     pub document_id: Option<String>,
     pub peers: Vec<String>,
+    pub archived_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "agreement_status", rename_all = "lowercase")]
+pub enum AgreementStatus {
+    Proposed,
+    Countered,
+    Accepted,
+    Rejected,
+}
+
+/// Which side of a request authored an agreement revision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "agreement_party", rename_all = "lowercase")]
+pub enum AgreementParty {
+    Requester,
+    Peer,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Agreement {
+    pub id: Uuid,
+    pub request_id: Uuid,
+    pub peer_name: String,
+    pub status: AgreementStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The five elements of a Win-Win Performance Agreement.
+#[derive(Debug, Deserialize)]
+pub struct AgreementTerms {
+    pub results: String,
+    pub guidelines: String,
+    pub resources: String,
+    pub accountability: String,
+    pub consequences: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AgreementRevision {
+    pub id: Uuid,
+    pub agreement_id: Uuid,
+    pub version: i32,
+    pub results: String,
+    pub guidelines: String,
+    pub resources: String,
+    pub accountability: String,
+    pub consequences: String,
+    pub proposed_by: Uuid,
+    pub proposed_by_party: AgreementParty,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgreementWithRevisions {
+    #[serde(flatten)]
+    pub agreement: Agreement,
+    pub revisions: Vec<AgreementRevision>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct EscalationSettings {
+    pub stalled_after_days: i32,
+    pub critical_after_days: i32,
+}
+
+impl Default for EscalationSettings {
+    fn default() -> Self {
+        Self {
+            stalled_after_days: crate::escalation::DEFAULT_STALLED_AFTER_DAYS,
+            critical_after_days: crate::escalation::DEFAULT_CRITICAL_AFTER_DAYS,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct TrustScore {
     pub id: Uuid,
@@ -105,6 +333,49 @@ pub struct TrustScore {
     pub score: i32,
     pub status: String,
     pub updated_at: DateTime<Utc>,
+    pub policy_version: Option<i32>,
+    pub tier: TrustTier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "trust_event_type", rename_all = "snake_case")]
+pub enum TrustEventType {
+    RequestCompleted,
+    RequestStalled,
+    RequestCritical,
+    Interaction,
+    PeerAdded,
+    Activity,
+    Decay,
+    /// Balances the ledger's points after a new scoring policy is activated.
+    Repricing,
+}
+
+/// One entry in the trust ledger. `points` is what the event contributed to
+/// the score when it was recorded.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct TrustEvent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub event_type: TrustEventType,
+    pub quantity: i32,
+    pub points: i32,
+    pub source_type: Option<String>,
+    pub source_id: Option<Uuid>,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrustEventQuery {
+    pub event_type: Option<TrustEventType>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -112,11 +383,294 @@ pub struct NetworkPeer {
     pub id: Uuid,
     pub user_id: Uuid,
     pub peer_name: String,
+    /// Set when the peer is a registered user who accepted the link.
+    pub peer_user_id: Option<Uuid>,
+    /// The account the owner asked to link, until that user responds.
+    pub pending_user_id: Option<Uuid>,
     pub trust_level: String,
     pub interactions: i32,
     pub last_interaction: DateTime<Utc>,
     pub position_x: f64,
     pub position_y: f64,
+    pub relationship_score: Option<i32>,
+    /// The peer's tier, when the peer is a registered user.
+    #[sqlx(default)]
+    pub tier: Option<TrustTier>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LayoutQuery {
+    /// Defaults to a seed derived from the user's id.
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateNetworkPeerBody {
+    pub peer_name: String,
+    /// An account to ask to link to the peer.
+    pub peer_user_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateNetworkPeerBody {
+    pub peer_name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub peer_user_id: Option<Option<Uuid>>,
+}
+
+/// Someone asking to link one of their peers to the caller's account.
+#[derive(Debug, Serialize, FromRow)]
+pub struct PeerLinkRequest {
+    pub peer_id: Uuid,
+    pub requester_id: Uuid,
+    pub requester_username: String,
+    /// What the requester calls the caller in their network.
+    pub peer_name: String,
+    pub requested_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkResponseBody {
+    pub accept: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    #[default]
+    Json,
+    Graphml,
+    Gexf,
+    Dot,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphQuery {
+    pub format: Option<GraphFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphNodeKind {
+    User,
+    Peer,
+}
+
+impl GraphNodeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GraphNodeKind::User => "user",
+            GraphNodeKind::Peer => "peer",
+        }
+    }
+}
+
+/// A node in the network graph: the user at the origin, or one of their
+/// peers at its map position.
+#[derive(Debug, Serialize)]
+pub struct GraphNode {
+    pub id: Uuid,
+    pub label: String,
+    pub kind: GraphNodeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trust_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relationship_score: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<TrustTier>,
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphEdgeKind {
+    /// Between the user and one of their peers.
+    Direct,
+    /// Between two peers.
+    Peer,
+}
+
+impl GraphEdgeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GraphEdgeKind::Direct => "direct",
+            GraphEdgeKind::Peer => "peer",
+        }
+    }
+}
+
+/// An undirected edge. Direct edges are weighted by the user's interactions
+/// with the peer; peer edges by the requests both peers are tagged on plus,
+/// when both are registered users, the interactions in their own networks;
+/// every peer edge weighs at least 1.
+#[derive(Debug, Serialize)]
+pub struct GraphEdge {
+    pub source: Uuid,
+    pub target: Uuid,
+    pub kind: GraphEdgeKind,
+    pub weight: i32,
+    pub shared_requests: i32,
+    pub linked_interactions: i32,
+    pub last_interaction: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NetworkGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Centrality of one peer within the peer-to-peer graph. The user is left out
+/// of the analysis, since every peer is directly connected to them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PeerCentrality {
+    pub peer_id: Uuid,
+    pub peer_name: String,
+    pub degree: i32,
+    /// Sum of the weights of the peer's edges.
+    pub weighted_degree: i32,
+    /// Degree as a fraction of the other peers.
+    pub degree_centrality: f64,
+    /// Normalized to 0..1: the share of shortest paths between other peers
+    /// that pass through this one.
+    pub betweenness: f64,
+    pub community: i32,
+    pub is_broker: bool,
+    /// Removing this peer would split the graph.
+    pub is_articulation_point: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Community {
+    pub id: i32,
+    pub size: i32,
+    pub members: Vec<Uuid>,
+    /// Total weight of the edges inside the community.
+    pub internal_weight: i32,
+}
+
+/// A peer whose connections span more than one community.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Broker {
+    pub peer_id: Uuid,
+    pub peer_name: String,
+    pub betweenness: f64,
+    pub communities_bridged: Vec<i32>,
+}
+
+/// An edge whose removal would disconnect part of the graph.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BridgeEdge {
+    pub source: Uuid,
+    pub target: Uuid,
+    pub weight: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetworkAnalytics {
+    pub node_count: i32,
+    pub edge_count: i32,
+    pub modularity: f64,
+    pub peers: Vec<PeerCentrality>,
+    pub communities: Vec<Community>,
+    pub brokers: Vec<Broker>,
+    pub bridges: Vec<BridgeEdge>,
+    pub computed_at: DateTime<Utc>,
+    /// Whether the result came from the cache rather than being computed for
+    /// this request.
+    #[serde(default)]
+    pub cached: bool,
+}
+
+/// What a relationship score is computed from. Shared requests are the
+/// owner's requests the peer is tagged on.
+#[derive(Debug, Serialize, FromRow)]
+pub struct RelationshipFactors {
+    pub shared_completed: i32,
+    pub shared_stalled: i32,
+    pub shared_critical: i32,
+    pub shared_open: i32,
+    pub interactions: i32,
+    pub days_since_interaction: i32,
+    pub agreements_accepted: i32,
+    pub agreements_rejected: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelationshipComponent {
+    pub factor: &'static str,
+    pub points: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelationshipScore {
+    pub score: i32,
+    pub trust_level: String,
+    pub factors: RelationshipFactors,
+    pub breakdown: Vec<RelationshipComponent>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NetworkPeerDetail {
+    #[serde(flatten)]
+    pub peer: NetworkPeer,
+    pub relationship: RelationshipScore,
+}
+
+/// The data a Five Waves of Trust profile is computed from. For a peer the
+/// request and agreement counts cover the owner's requests the peer is tagged
+/// on; for the user themselves they cover everything the user takes part in.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WaveInputs {
+    pub requests_total: i32,
+    pub requests_completed: i32,
+    pub requests_stalled: i32,
+    pub requests_critical: i32,
+    pub agreements_accepted: i32,
+    pub agreements_rejected: i32,
+    pub agreements_pending: i32,
+    pub interactions: i32,
+    pub relationship_score: Option<i32>,
+    /// How many other users have this subject's account linked in their
+    /// network; always 0 for peers without an account.
+    pub market_reach: i32,
+    /// Unread alerts on the requests this subject is involved in.
+    pub open_alerts: i32,
+    pub trust_score: Option<i32>,
+    pub activity_points: i32,
+}
+
+/// Scores from 0 to 100 for each of the five waves.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TrustWaves {
+    pub self_trust: i32,
+    pub relationship_trust: i32,
+    pub organizational_trust: i32,
+    pub market_trust: i32,
+    pub societal_trust: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrustWaveProfile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_name: Option<String>,
+    pub waves: TrustWaves,
+    pub overall: i32,
+    /// Waves at or above the strength threshold, strongest first.
+    pub strengths: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<WaveInputs>,
+    pub computed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrustWaveSummary {
+    #[serde(rename = "self")]
+    pub own: TrustWaveProfile,
+    pub peers: Vec<TrustWaveProfile>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -126,6 +680,8 @@ pub struct Alert {
     pub title: String,
     pub message: String,
     pub alert_type: String,
+    /// The request a request alert is about.
+    pub request_id: Option<Uuid>,
     pub is_read: bool,
     pub created_at: DateTime<Utc>,
 }
@@ -133,22 +689,439 @@ pub struct Alert {
 #[derive(Debug, Deserialize)]
 pub struct AlertFilter {
     pub filter: Option<String>,
+    pub is_read: Option<bool>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NetworkPeerQuery {
+    pub trust_level: Option<String>,
+    /// Case-insensitive match on the peer's name.
+    pub q: Option<String>,
+    pub interacted_from: Option<DateTime<Utc>>,
+    pub interacted_to: Option<DateTime<Utc>>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+/// The badge shown next to a user. Variants are declared lowest first so
+/// tiers compare by rank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "trust_tier", rename_all = "lowercase")]
+pub enum TrustTier {
+    Critical,
+    Fair,
+    Trusted,
+}
+
+impl TrustTier {
+    pub fn label(self) -> &'static str {
+        match self {
+            TrustTier::Critical => "Critical",
+            TrustTier::Fair => "Fair",
+            TrustTier::Trusted => "Trusted",
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct TrustScoreComputation {
     pub score: i32,
     pub status: String,
+    pub tier: TrustTier,
+    pub policy_version: i32,
     pub factors: TrustFactors,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Copy, Default, Serialize)]
 pub struct TrustFactors {
     pub completed_requests: i32,
     pub stalled_requests: i32,
     pub critical_requests: i32,
     pub total_interactions: i32,
     pub peer_count: i32,
+    pub activity_points: i32,
+    /// Points lost to inactivity decay; zero or negative.
+    pub decay_points: i32,
+    /// Whole days since the user's last engagement with the ledger.
+    pub days_inactive: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryBucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl HistoryBucket {
+    /// The `date_trunc` field name for this bucket.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistoryBucket::Day => "day",
+            HistoryBucket::Week => "week",
+            HistoryBucket::Month => "month",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrustScoreHistoryQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub bucket: Option<HistoryBucket>,
+}
+
+/// The last score recorded in one bucket of the history series.
+#[derive(Debug, Serialize, FromRow)]
+pub struct TrustScorePoint {
+    pub bucket_start: DateTime<Utc>,
+    pub score: i32,
+    pub status: String,
+    pub policy_version: Option<i32>,
+    /// What wrote the snapshot: `recalculation`, `decay` or `daily`.
+    pub source: String,
+    #[sqlx(skip)]
+    pub delta: Option<i32>,
+    #[sqlx(skip)]
+    pub moving_average: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatusTransition {
+    pub at: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+    pub score: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrustScoreHistory {
+    pub bucket: HistoryBucket,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub moving_average_window: usize,
+    pub points: Vec<TrustScorePoint>,
+    pub transitions: Vec<StatusTransition>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimezoneSettings {
+    /// An IANA zone name such as `Europe/Berlin`.
+    pub timezone: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContactDiscoverySettings {
+    pub contact_discovery: Option<bool>,
+    /// E.164, e.g. `+14155550123`; null removes it.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub phone: Option<Option<String>>,
+}
+
+/// How devices must hash identifiers before syncing them:
+/// `hex(sha256(salt + ":" + identifier))`, with emails trimmed and
+/// lowercased and phone numbers in E.164.
+#[derive(Debug, Serialize)]
+pub struct ContactHashing {
+    pub algorithm: &'static str,
+    pub encoding: &'static str,
+    pub salt: String,
+    /// What's hashed for an email address.
+    pub email_preimage: &'static str,
+    /// What's hashed for a phone number: the number as the server stores
+    /// it, `+` and digits only.
+    pub phone_preimage: &'static str,
+    pub max_identifiers_per_sync: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContactSyncBody {
+    #[serde(default)]
+    pub email_hashes: Vec<String>,
+    #[serde(default)]
+    pub phone_hashes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContactSuggestion {
+    pub user_id: Uuid,
+    pub username: String,
+    pub matched_email: bool,
+    pub matched_phone: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContactSyncResult {
+    /// Matched users who aren't in the caller's network yet.
+    pub suggestions: Vec<ContactSuggestion>,
+    pub already_in_network: i32,
+    pub syncs_remaining: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "activity_type", rename_all = "snake_case")]
+pub enum ActivityType {
+    ChatSent,
+    QuestionAsked,
+    RequestViewed,
+    ArticleRead,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityEventInput {
+    /// Client-generated id; resending an event with the same id is a no-op.
+    pub id: String,
+    #[serde(rename = "type")]
+    pub activity_type: ActivityType,
+    /// What the activity was about, e.g. the request or article id.
+    pub target_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityBatch {
+    pub events: Vec<ActivityEventInput>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityOutcome {
+    Accepted,
+    /// An event id already recorded; nothing was stored.
+    Duplicate,
+    /// A target already rewarded today; recorded, but for no points.
+    Repeat,
+    Capped,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivityResult {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub activity_type: ActivityType,
+    pub outcome: ActivityOutcome,
+    pub points: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivityResponse {
+    pub activity_date: chrono::NaiveDate,
+    pub points_awarded: i32,
+    pub results: Vec<ActivityResult>,
+    pub trust_score: Option<TrustScoreComputation>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyticsPeriod {
+    Week,
+    #[default]
+    Month,
+    Quarter,
+    Year,
+}
+
+impl AnalyticsPeriod {
+    pub fn days(self) -> i64 {
+        match self {
+            AnalyticsPeriod::Week => 7,
+            AnalyticsPeriod::Month => 30,
+            AnalyticsPeriod::Quarter => 90,
+            AnalyticsPeriod::Year => 365,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsQuery {
+    pub period: Option<AnalyticsPeriod>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DateRange {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+/// A measure for the current period next to the one before it. Values are
+/// `None` when there is nothing to measure, e.g. no completed requests.
+#[derive(Debug, Serialize)]
+pub struct MetricComparison {
+    pub current: Option<f64>,
+    pub previous: Option<f64>,
+    pub change: Option<f64>,
+    pub change_pct: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeadMeasures {
+    pub requests_initiated: MetricComparison,
+    pub agreements_proposed: MetricComparison,
+    pub follow_ups: MetricComparison,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LagMeasures {
+    pub completion_rate: MetricComparison,
+    pub mean_hours_to_complete: MetricComparison,
+    pub stalled_ratio: MetricComparison,
+}
+
+/// Time requests spent stalled or critical within the period, and the trust
+/// points lost over it.
+#[derive(Debug, Serialize)]
+pub struct TrustTax {
+    pub stalled_hours: MetricComparison,
+    pub critical_hours: MetricComparison,
+    pub total_hours: MetricComparison,
+    pub points_lost: MetricComparison,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnalyticsSummary {
+    pub period: AnalyticsPeriod,
+    pub current: DateRange,
+    pub previous: DateRange,
+    pub lead: LeadMeasures,
+    pub lag: LagMeasures,
+    pub trust_tax: TrustTax,
+}
+
+/// A hypothetical change to run through the trust score simulator.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SimulatedChange {
+    CompleteRequest { request_id: Uuid },
+    /// Moves a stalled or critical request back to fair.
+    ResolveRequest { request_id: Uuid },
+    AddInteractions { count: i32 },
+    AddPeers { count: i32 },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulationBody {
+    pub changes: Vec<SimulatedChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FactorDiff {
+    pub factor: &'static str,
+    pub current: i32,
+    pub projected: i32,
+    /// Score points the change in this factor is worth.
+    pub points: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulationResult {
+    pub current: TrustScoreComputation,
+    pub projected: TrustScoreComputation,
+    pub score_change: i32,
+    pub status_changed: bool,
+    pub tier_changed: bool,
+    pub factor_diff: Vec<FactorDiff>,
+    /// Caveats about changes that wouldn't affect the score, or couldn't be
+    /// made as described.
+    pub notes: Vec<String>,
+}
+
+/// The tunable numbers behind a trust score. Penalties are stored as positive
+/// amounts and subtracted.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScoringWeights {
+    pub base_score: i32,
+    pub completed_points: i32,
+    pub stalled_penalty: i32,
+    pub critical_penalty: i32,
+    pub interaction_multiplier: f64,
+    pub peer_points: i32,
+    pub healthy_threshold: i32,
+    pub fair_threshold: i32,
+    #[serde(default = "default_trusted_threshold")]
+    pub trusted_threshold: i32,
+    /// How far below a tier's threshold a score must fall before the user
+    /// is demoted out of it.
+    #[serde(default = "default_tier_hysteresis")]
+    pub tier_hysteresis: i32,
+    /// Days without engagement before decay starts.
+    #[serde(default = "default_decay_grace_days")]
+    pub decay_grace_days: i32,
+    /// Days for the points above the base score to halve once decay has
+    /// started. 0 disables decay.
+    #[serde(default = "default_decay_half_life_days")]
+    pub decay_half_life_days: i32,
+}
+
+fn default_trusted_threshold() -> i32 {
+    350
+}
+
+fn default_tier_hysteresis() -> i32 {
+    25
+}
+
+fn default_decay_grace_days() -> i32 {
+    14
+}
+
+fn default_decay_half_life_days() -> i32 {
+    60
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ScoringPolicy {
+    pub version: i32,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub weights: ScoringWeights,
+    pub is_active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PolicyActivation {
+    pub policy: ScoringPolicy,
+    pub users_rescored: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PolicyPreviewEntry {
+    pub user_id: Uuid,
+    pub username: String,
+    pub current_score: Option<i32>,
+    pub current_status: Option<String>,
+    pub projected_score: i32,
+    pub projected_status: String,
+    pub current_tier: Option<TrustTier>,
+    pub projected_tier: TrustTier,
+    pub delta: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PolicyPreview {
+    pub active_version: i32,
+    pub users: usize,
+    pub status_changes: usize,
+    pub average_delta: f64,
+    pub entries: Vec<PolicyPreviewEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PageMeta {
+    pub next_cursor: Option<String>,
+    pub total: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -156,6 +1129,14 @@ pub struct ApiResponse<T: Serialize> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Vec<FieldError>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<PageMeta>,
 }
 
 impl<T: Serialize> ApiResponse<T> {
@@ -164,6 +1145,17 @@ impl<T: Serialize> ApiResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            code: None,
+            details: None,
+            correlation_id: None,
+            pagination: None,
+        }
+    }
+
+    pub fn paginated(data: T, meta: PageMeta) -> Self {
+        Self {
+            pagination: Some(meta),
+            ..Self::ok(data)
         }
     }
 
@@ -172,6 +1164,15 @@ impl<T: Serialize> ApiResponse<T> {
             success: false,
             data: None,
             error: Some(msg.to_string()),
+            code: None,
+            details: None,
+            correlation_id: None,
+            pagination: None,
         }
     }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
 }
@@ -0,0 +1,456 @@
+use actix_web::{web, HttpResponse};
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, FieldError};
+use crate::layout;
+use crate::models::*;
+use crate::relationships;
+use crate::trust::{self, NewTrustEvent};
+
+pub const MAX_PEER_NAME_LEN: usize = 100;
+
+fn validate_peer_name(name: &str, errors: &mut Vec<FieldError>) {
+    if name.trim().is_empty() {
+        errors.push(FieldError::new("peer_name", "Peer name is required"));
+    } else if name.trim().chars().count() > MAX_PEER_NAME_LEN {
+        errors.push(FieldError::new(
+            "peer_name",
+            format!("Peer name must be at most {} characters", MAX_PEER_NAME_LEN),
+        ));
+    }
+}
+
+async fn fetch_peer(conn: impl PgExecutor<'_>, user_id: Uuid, peer_id: Uuid) -> Result<NetworkPeer, AppError> {
+    sqlx::query_as::<_, NetworkPeer>(
+        "SELECT np.*, pts.tier
+         FROM network_peers np
+         LEFT JOIN trust_scores pts ON pts.user_id = np.peer_user_id
+         WHERE np.id = $1 AND np.user_id = $2"
+    )
+    .bind(peer_id)
+    .bind(user_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or(AppError::NotFound("Peer"))
+}
+
+/// Checks that `account_id` is someone else's account. Nothing about the
+/// account is returned: the owner only learns more once the user accepts.
+async fn ensure_account_exists(conn: &mut PgConnection, user_id: Uuid, account_id: Uuid) -> Result<(), AppError> {
+    if account_id == user_id {
+        return Err(AppError::invalid("peer_user_id", "You can't add yourself as a peer"));
+    }
+
+    let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM users WHERE id = $1")
+        .bind(account_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    match exists {
+        Some(_) => Ok(()),
+        None => Err(AppError::invalid("peer_user_id", "No user with this id exists")),
+    }
+}
+
+async fn ensure_name_free(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    name: &str,
+    except: Option<Uuid>,
+) -> Result<(), AppError> {
+    let taken: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT id FROM network_peers
+         WHERE user_id = $1 AND LOWER(peer_name) = LOWER($2) AND ($3::uuid IS NULL OR id <> $3)"
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(except)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match taken {
+        Some(_) => Err(AppError::Conflict(format!("\"{}\" is already in your network", name))),
+        None => Ok(()),
+    }
+}
+
+async fn ensure_account_free(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    account_id: Uuid,
+    except: Option<Uuid>,
+) -> Result<(), AppError> {
+    let taken: Option<(String,)> = sqlx::query_as(
+        "SELECT peer_name FROM network_peers
+         WHERE user_id = $1 AND $2 IN (peer_user_id, pending_user_id) AND ($3::uuid IS NULL OR id <> $3)"
+    )
+    .bind(user_id)
+    .bind(account_id)
+    .bind(except)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match taken {
+        Some((name,)) => Err(AppError::Conflict(format!("This user is already in your network as \"{}\"", name))),
+        None => Ok(()),
+    }
+}
+
+/// Checks that none of the owner's requests is tagged with both `from` and
+/// `to`, which a rename from one to the other would merge into one tag.
+async fn ensure_tags_free(conn: &mut PgConnection, user_id: Uuid, from: &str, to: &str) -> Result<(), AppError> {
+    let clash: Option<(String,)> = sqlx::query_as(
+        "SELECT r.title FROM requests r
+         JOIN request_peers a ON a.request_id = r.id AND a.peer_name = $2
+         JOIN request_peers b ON b.request_id = r.id AND b.peer_name = $3
+         WHERE r.user_id = $1
+         ORDER BY r.title
+         LIMIT 1"
+    )
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match clash {
+        Some((title,)) => Err(AppError::Conflict(format!(
+            "\"{}\" is already tagged with both \"{}\" and \"{}\"; remove one tag first",
+            title, from, to
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Maps each name to the spelling used by the owner's matching network peer,
+/// if there is one, so request tags and peers always agree on a name.
+pub async fn canonical_peer_names(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    names: &[String],
+) -> Result<Vec<String>, sqlx::Error> {
+    let known: Vec<(String,)> = sqlx::query_as(
+        "SELECT peer_name FROM network_peers
+         WHERE user_id = $1 AND LOWER(peer_name) = ANY(SELECT LOWER(n) FROM UNNEST($2::text[]) AS n)"
+    )
+    .bind(user_id)
+    .bind(names)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut canonical: Vec<String> = Vec::with_capacity(names.len());
+    for name in names {
+        let name = known
+            .iter()
+            .find(|(k,)| k.to_lowercase() == name.to_lowercase())
+            .map_or_else(|| name.clone(), |(k,)| k.clone());
+        if !canonical.contains(&name) {
+            canonical.push(name);
+        }
+    }
+    Ok(canonical)
+}
+
+/// Counts tagging each peer in `names` on `request_id` as an interaction,
+/// adding anyone not yet in the owner's network. An interaction is credited
+/// once per request and peer, however often the tag is removed and re-added,
+/// and peers added this way don't earn the points for adding a peer. The
+/// caller rebuilds the owner's trust score afterwards.
+pub async fn record_tag_interactions(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    request_id: Uuid,
+    names: &[String],
+) -> Result<(), sqlx::Error> {
+    for name in names {
+        let (peer_id, peer_name): (Uuid, String) = sqlx::query_as(
+            "INSERT INTO network_peers (id, user_id, peer_name, interactions, last_interaction)
+             VALUES ($1, $2, $3, 0, NOW())
+             ON CONFLICT (user_id, LOWER(peer_name)) DO UPDATE SET peer_name = network_peers.peer_name
+             RETURNING id, peer_name"
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        let key = format!("tag:{}:{}", request_id, peer_name.to_lowercase());
+        let interacted = NewTrustEvent {
+            event_type: TrustEventType::Interaction,
+            quantity: 1,
+            source_type: "network_peer",
+            source_id: Some(peer_id),
+            description: &peer_name,
+        };
+        if trust::append_event_once(conn, user_id, &key, interacted).await? {
+            sqlx::query(
+                "UPDATE network_peers SET interactions = interactions + 1, last_interaction = NOW() WHERE id = $1"
+            )
+            .bind(peer_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn create_network_peer(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    body: web::Json<CreateNetworkPeerBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+
+    let mut errors = Vec::new();
+    validate_peer_name(&body.peer_name, &mut errors);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let name = body.peer_name.trim().to_string();
+    ensure_name_free(&mut tx, user_id, &name, None).await?;
+    if let Some(account_id) = body.peer_user_id {
+        ensure_account_exists(&mut tx, user_id, account_id).await?;
+        ensure_account_free(&mut tx, user_id, account_id, None).await?;
+    }
+
+    let peer_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO network_peers (id, user_id, peer_name, pending_user_id, link_requested_at, interactions)
+         VALUES ($1, $2, $3, $4, CASE WHEN $4::uuid IS NOT NULL THEN NOW() END, 0)"
+    )
+    .bind(peer_id)
+    .bind(user_id)
+    .bind(&name)
+    .bind(body.peer_user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let added = NewTrustEvent {
+        event_type: TrustEventType::PeerAdded,
+        quantity: 1,
+        source_type: "network_peer",
+        source_id: Some(peer_id),
+        description: &name,
+    };
+    trust::record_event(&mut tx, user_id, added).await?;
+    // Requests may already be tagged with this name.
+    relationships::refresh_relationship_scores(&mut tx, Some(user_id)).await?;
+
+    let peer = fetch_peer(&mut *tx, user_id, peer_id).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(peer)))
+}
+
+/// Renames a peer and/or changes the account it's linked to. A rename is
+/// carried over to the owner's request tags and agreements so they keep
+/// matching the peer. A new account is only asked to link; the link takes
+/// effect once that user accepts.
+pub async fn update_network_peer(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateNetworkPeerBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let peer_id = path.into_inner();
+
+    let mut errors = Vec::new();
+    if let Some(name) = &body.peer_name {
+        validate_peer_name(name, &mut errors);
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let mut tx = pool.begin().await?;
+    let existing = fetch_peer(&mut *tx, user_id, peer_id).await?;
+
+    let name = body.peer_name.as_deref().map(str::trim).unwrap_or(&existing.peer_name).to_string();
+    if name != existing.peer_name {
+        ensure_name_free(&mut tx, user_id, &name, Some(peer_id)).await?;
+        ensure_tags_free(&mut tx, user_id, &existing.peer_name, &name).await?;
+    }
+
+    let (peer_user_id, pending_user_id) = match body.peer_user_id {
+        Some(Some(account_id)) if existing.peer_user_id == Some(account_id) => (Some(account_id), None),
+        Some(Some(account_id)) => {
+            ensure_account_exists(&mut tx, user_id, account_id).await?;
+            ensure_account_free(&mut tx, user_id, account_id, Some(peer_id)).await?;
+            (None, Some(account_id))
+        }
+        Some(None) => (None, None),
+        None => (existing.peer_user_id, existing.pending_user_id),
+    };
+
+    sqlx::query(
+        "UPDATE network_peers
+         SET peer_name = $1, peer_user_id = $2, pending_user_id = $3,
+             link_requested_at = CASE
+                 WHEN $3::uuid IS NULL THEN NULL
+                 WHEN $3 IS DISTINCT FROM pending_user_id THEN NOW()
+                 ELSE link_requested_at
+             END
+         WHERE id = $4"
+    )
+    .bind(&name)
+    .bind(peer_user_id)
+    .bind(pending_user_id)
+    .bind(peer_id)
+    .execute(&mut *tx)
+    .await?;
+
+    if name != existing.peer_name {
+        sqlx::query(
+            "UPDATE request_peers rp SET peer_name = $1
+             FROM requests r
+             WHERE r.id = rp.request_id AND r.user_id = $2 AND rp.peer_name = $3"
+        )
+        .bind(&name)
+        .bind(user_id)
+        .bind(&existing.peer_name)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE agreements a SET peer_name = $1
+             FROM requests r
+             WHERE r.id = a.request_id AND r.user_id = $2 AND a.peer_name = $3"
+        )
+        .bind(&name)
+        .bind(user_id)
+        .bind(&existing.peer_name)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let peer = fetch_peer(&mut *tx, user_id, peer_id).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(peer)))
+}
+
+/// Removes a peer from the network. Request tags are left alone, so tagging
+/// the same name again adds the peer back.
+pub async fn delete_network_peer(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let mut tx = pool.begin().await?;
+
+    let (peer_id, peer_name): (Uuid, String) = sqlx::query_as(
+        "DELETE FROM network_peers WHERE id = $1 AND user_id = $2 RETURNING id, peer_name"
+    )
+    .bind(path.into_inner())
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound("Peer"))?;
+
+    // Only take back the points the peer actually earned; peers added by
+    // tagging never earned any.
+    let (credited,): (i64,) = sqlx::query_as(
+        "SELECT COALESCE(SUM(quantity), 0) FROM trust_events
+         WHERE user_id = $1 AND event_type = 'peer_added' AND source_id = $2"
+    )
+    .bind(user_id)
+    .bind(peer_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if credited > 0 {
+        let description = format!("Removed {}", peer_name);
+        let removed = NewTrustEvent {
+            event_type: TrustEventType::PeerAdded,
+            quantity: -(credited as i32),
+            source_type: "network_peer",
+            source_id: Some(peer_id),
+            description: &description,
+        };
+        trust::record_event(&mut tx, user_id, removed).await?;
+    }
+    layout::refresh_layout(&mut tx, user_id, layout::default_seed(user_id), true).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"deleted": true}))))
+}
+
+/// Lists the peers other users asked to link to the caller's account.
+pub async fn list_link_requests(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let requests = sqlx::query_as::<_, PeerLinkRequest>(
+        "SELECT np.id AS peer_id, np.user_id AS requester_id, u.username AS requester_username,
+                np.peer_name, np.link_requested_at AS requested_at
+         FROM network_peers np
+         JOIN users u ON u.id = np.user_id
+         WHERE np.pending_user_id = $1
+         ORDER BY np.link_requested_at DESC, np.id"
+    )
+    .bind(user.id)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(requests)))
+}
+
+/// Accepts or declines a request to link someone's peer to the caller's
+/// account. Only an accepted link shares the caller's tier and lets them act
+/// as that peer on the owner's requests.
+pub async fn respond_to_link_request(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    body: web::Json<LinkResponseBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let mut tx = pool.begin().await?;
+
+    let (peer_id, owner_id): (Uuid, Uuid) = sqlx::query_as(
+        "SELECT id, user_id FROM network_peers WHERE id = $1 AND pending_user_id = $2 FOR UPDATE"
+    )
+    .bind(path.into_inner())
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound("Link request"))?;
+
+    let accepted = body.accept;
+    if accepted {
+        let already: Option<(Uuid,)> = sqlx::query_as(
+            "SELECT id FROM network_peers WHERE user_id = $1 AND peer_user_id = $2"
+        )
+        .bind(owner_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if already.is_some() {
+            return Err(AppError::Conflict("You're already linked to another peer in this network".to_string()));
+        }
+    }
+
+    sqlx::query(
+        "UPDATE network_peers
+         SET peer_user_id = CASE WHEN $2 THEN pending_user_id END,
+             pending_user_id = NULL, link_requested_at = NULL
+         WHERE id = $1"
+    )
+    .bind(peer_id)
+    .bind(accepted)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"linked": accepted}))))
+}
@@ -0,0 +1,481 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::types::Json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::AppError;
+use crate::graph;
+use crate::models::*;
+
+/// Part of the cache key, so cached results are recomputed when the
+/// analysis itself changes.
+const ANALYSIS_VERSION: &str = "2";
+const MAX_PROPAGATION_ROUNDS: usize = 100;
+
+/// The peer-to-peer part of the network graph, with peers indexed in id
+/// order.
+struct PeerGraph {
+    ids: Vec<Uuid>,
+    names: Vec<String>,
+    adjacency: Vec<Vec<(usize, i32)>>,
+    edges: Vec<(usize, usize, i32)>,
+}
+
+impl PeerGraph {
+    fn from_graph(graph: &NetworkGraph) -> Self {
+        let mut peers: Vec<&GraphNode> = graph.nodes.iter().filter(|n| n.kind == GraphNodeKind::Peer).collect();
+        peers.sort_by_key(|n| n.id);
+
+        let index: HashMap<Uuid, usize> = peers.iter().enumerate().map(|(i, n)| (n.id, i)).collect();
+        let mut adjacency = vec![Vec::new(); peers.len()];
+        let mut edges = Vec::new();
+        for edge in graph.edges.iter().filter(|e| e.kind == GraphEdgeKind::Peer) {
+            if let (Some(&a), Some(&b)) = (index.get(&edge.source), index.get(&edge.target)) {
+                adjacency[a].push((b, edge.weight));
+                adjacency[b].push((a, edge.weight));
+                edges.push((a, b, edge.weight));
+            }
+        }
+        for neighbours in &mut adjacency {
+            neighbours.sort_unstable();
+        }
+
+        PeerGraph {
+            ids: peers.iter().map(|n| n.id).collect(),
+            names: peers.iter().map(|n| n.label.clone()).collect(),
+            adjacency,
+            edges,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+fn round4(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+/// Hashes everything the analysis depends on. Map positions are left out, so
+/// re-laying out the network doesn't invalidate the cache.
+fn fingerprint(graph: &NetworkGraph) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(ANALYSIS_VERSION.as_bytes());
+    for node in graph.nodes.iter().filter(|n| n.kind == GraphNodeKind::Peer) {
+        hasher.update(format!("\nn {} {}", node.id, node.label).as_bytes());
+    }
+    for edge in graph.edges.iter().filter(|e| e.kind == GraphEdgeKind::Peer) {
+        hasher.update(format!("\ne {} {} {}", edge.source, edge.target, edge.weight).as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Brandes' algorithm over unweighted shortest paths, normalized so a peer
+/// on every shortest path between the others scores 1.
+fn betweenness(g: &PeerGraph) -> Vec<f64> {
+    let n = g.len();
+    let mut centrality = vec![0.0; n];
+
+    for source in 0..n {
+        let mut stack = Vec::with_capacity(n);
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut paths = vec![0.0; n];
+        let mut distance: Vec<Option<usize>> = vec![None; n];
+        paths[source] = 1.0;
+        distance[source] = Some(0);
+
+        let mut queue = VecDeque::from([source]);
+        while let Some(v) = queue.pop_front() {
+            stack.push(v);
+            let next = distance[v].map(|d| d + 1);
+            for &(w, _) in &g.adjacency[v] {
+                if distance[w].is_none() {
+                    distance[w] = next;
+                    queue.push_back(w);
+                }
+                if distance[w] == next {
+                    paths[w] += paths[v];
+                    predecessors[w].push(v);
+                }
+            }
+        }
+
+        let mut dependency = vec![0.0; n];
+        while let Some(w) = stack.pop() {
+            for &v in &predecessors[w] {
+                dependency[v] += paths[v] / paths[w] * (1.0 + dependency[w]);
+            }
+            if w != source {
+                centrality[w] += dependency[w];
+            }
+        }
+    }
+
+    // Each pair was counted from both ends.
+    let pairs = if n > 2 { ((n - 1) * (n - 2)) as f64 } else { 1.0 };
+    centrality.into_iter().map(|c| round4(c / pairs)).collect()
+}
+
+/// Weighted label propagation. Peers are visited in id order and ties keep
+/// the current label or else take the lowest, so the result is deterministic.
+/// Each edge's weight is scaled by one plus the neighbours its ends share, so
+/// a lone edge between two tight groups doesn't drag one into the other.
+/// Communities are numbered from 0 by size, largest first.
+fn detect_communities(g: &PeerGraph) -> Vec<usize> {
+    let n = g.len();
+    let mut labels: Vec<usize> = (0..n).collect();
+
+    let strength: Vec<Vec<(usize, i32)>> = (0..n)
+        .map(|v| {
+            g.adjacency[v]
+                .iter()
+                .map(|&(w, weight)| {
+                    let shared = g.adjacency[v]
+                        .iter()
+                        .filter(|&&(x, _)| g.adjacency[w].binary_search_by_key(&x, |&(y, _)| y).is_ok())
+                        .count();
+                    (w, weight * (1 + shared as i32))
+                })
+                .collect()
+        })
+        .collect();
+
+    for _ in 0..MAX_PROPAGATION_ROUNDS {
+        let mut changed = false;
+        for v in 0..n {
+            let mut weights: HashMap<usize, i32> = HashMap::new();
+            for &(w, weight) in &strength[v] {
+                *weights.entry(labels[w]).or_default() += weight;
+            }
+            let Some(&best) = weights.values().max() else {
+                continue;
+            };
+            if weights.get(&labels[v]) == Some(&best) {
+                continue;
+            }
+            let label = weights
+                .iter()
+                .filter(|(_, &w)| w == best)
+                .map(|(&l, _)| l)
+                .min()
+                .unwrap_or(labels[v]);
+            labels[v] = label;
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut groups: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (v, &label) in labels.iter().enumerate() {
+        match groups.iter_mut().find(|(l, _)| *l == label) {
+            Some((_, members)) => members.push(v),
+            None => groups.push((label, vec![v])),
+        }
+    }
+    groups.sort_by_key(|(_, members)| (std::cmp::Reverse(members.len()), members[0]));
+
+    let mut community = vec![0; n];
+    for (id, (_, members)) in groups.iter().enumerate() {
+        for &v in members {
+            community[v] = id;
+        }
+    }
+    community
+}
+
+fn modularity(g: &PeerGraph, community: &[usize], count: usize) -> f64 {
+    let total: i32 = g.edges.iter().map(|&(_, _, w)| w).sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let m = total as f64;
+
+    let mut internal = vec![0.0; count];
+    let mut degree = vec![0.0; count];
+    for &(a, b, w) in &g.edges {
+        if community[a] == community[b] {
+            internal[community[a]] += w as f64;
+        }
+        degree[community[a]] += w as f64;
+        degree[community[b]] += w as f64;
+    }
+
+    let q: f64 = (0..count).map(|c| internal[c] / m - (degree[c] / (2.0 * m)).powi(2)).sum();
+    round4(q)
+}
+
+/// Tarjan's bridge and articulation point search.
+struct Cuts<'a> {
+    g: &'a PeerGraph,
+    timer: usize,
+    discovered: Vec<Option<usize>>,
+    low: Vec<usize>,
+    articulation: Vec<bool>,
+    bridges: Vec<(usize, usize, i32)>,
+}
+
+/// A peer on the search stack: the edge it was reached by and how far
+/// through its neighbours the search has got.
+struct Frame {
+    v: usize,
+    parent: Option<(usize, i32)>,
+    next: usize,
+    children: usize,
+}
+
+impl<'a> Cuts<'a> {
+    fn find(g: &'a PeerGraph) -> Self {
+        let n = g.len();
+        let mut cuts = Cuts {
+            g,
+            timer: 0,
+            discovered: vec![None; n],
+            low: vec![0; n],
+            articulation: vec![false; n],
+            bridges: Vec::new(),
+        };
+        for v in 0..n {
+            if cuts.discovered[v].is_none() {
+                cuts.visit(v);
+            }
+        }
+        cuts
+    }
+
+    fn discover(&mut self, v: usize) {
+        self.discovered[v] = Some(self.timer);
+        self.low[v] = self.timer;
+        self.timer += 1;
+    }
+
+    /// Depth-first search from `root` with an explicit stack, so long chains
+    /// of peers can't overflow the thread's stack.
+    fn visit(&mut self, root: usize) {
+        self.discover(root);
+        let mut stack = vec![Frame { v: root, parent: None, next: 0, children: 0 }];
+
+        while let Some(frame) = stack.last_mut() {
+            let v = frame.v;
+            if let Some(&(w, weight)) = self.g.adjacency[v].get(frame.next) {
+                frame.next += 1;
+                if frame.parent.map(|(p, _)| p) == Some(w) {
+                    continue;
+                }
+                match self.discovered[w] {
+                    Some(d) => self.low[v] = self.low[v].min(d),
+                    None => {
+                        frame.children += 1;
+                        self.discover(w);
+                        stack.push(Frame { v: w, parent: Some((v, weight)), next: 0, children: 0 });
+                    }
+                }
+                continue;
+            }
+
+            let Some(done) = stack.pop() else { break };
+            let Some((p, weight)) = done.parent else {
+                if done.children > 1 {
+                    self.articulation[v] = true;
+                }
+                continue;
+            };
+
+            self.low[p] = self.low[p].min(self.low[v]);
+            let discovered_p = self.discovered[p].unwrap_or(0);
+            let p_is_root = stack.last().is_some_and(|f| f.parent.is_none());
+            if !p_is_root && self.low[v] >= discovered_p {
+                self.articulation[p] = true;
+            }
+            if self.low[v] > discovered_p {
+                self.bridges.push((p.min(v), p.max(v), weight));
+            }
+        }
+    }
+}
+
+fn analyze(g: &PeerGraph) -> NetworkAnalytics {
+    let n = g.len();
+    let betweenness = betweenness(g);
+    let community = detect_communities(g);
+    let community_count = community.iter().max().map_or(0, |c| c + 1);
+    let cuts = Cuts::find(g);
+
+    let mut peers = Vec::with_capacity(n);
+    let mut brokers = Vec::new();
+    for v in 0..n {
+        let bridged: BTreeSet<usize> = std::iter::once(community[v])
+            .chain(g.adjacency[v].iter().map(|&(w, _)| community[w]))
+            .collect();
+        let is_broker = bridged.len() > 1 && (betweenness[v] > 0.0 || cuts.articulation[v]);
+
+        if is_broker {
+            brokers.push(Broker {
+                peer_id: g.ids[v],
+                peer_name: g.names[v].clone(),
+                betweenness: betweenness[v],
+                communities_bridged: bridged.iter().map(|&c| c as i32).collect(),
+            });
+        }
+
+        peers.push(PeerCentrality {
+            peer_id: g.ids[v],
+            peer_name: g.names[v].clone(),
+            degree: g.adjacency[v].len() as i32,
+            weighted_degree: g.adjacency[v].iter().map(|&(_, w)| w).sum(),
+            degree_centrality: if n > 1 { round4(g.adjacency[v].len() as f64 / (n - 1) as f64) } else { 0.0 },
+            betweenness: betweenness[v],
+            community: community[v] as i32,
+            is_broker,
+            is_articulation_point: cuts.articulation[v],
+        });
+    }
+
+    peers.sort_by(|a, b| {
+        b.betweenness
+            .total_cmp(&a.betweenness)
+            .then(b.degree.cmp(&a.degree))
+            .then_with(|| a.peer_name.cmp(&b.peer_name))
+    });
+    brokers.sort_by(|a, b| b.betweenness.total_cmp(&a.betweenness).then_with(|| a.peer_name.cmp(&b.peer_name)));
+
+    let communities = (0..community_count)
+        .map(|c| {
+            let members: Vec<usize> = (0..n).filter(|&v| community[v] == c).collect();
+            Community {
+                id: c as i32,
+                size: members.len() as i32,
+                members: members.iter().map(|&v| g.ids[v]).collect(),
+                internal_weight: g
+                    .edges
+                    .iter()
+                    .filter(|&&(a, b, _)| community[a] == c && community[b] == c)
+                    .map(|&(_, _, w)| w)
+                    .sum(),
+            }
+        })
+        .collect();
+
+    NetworkAnalytics {
+        node_count: n as i32,
+        edge_count: g.edges.len() as i32,
+        modularity: modularity(g, &community, community_count),
+        peers,
+        communities,
+        brokers,
+        bridges: cuts
+            .bridges
+            .iter()
+            .map(|&(a, b, weight)| BridgeEdge { source: g.ids[a], target: g.ids[b], weight })
+            .collect(),
+        computed_at: Utc::now(),
+        cached: false,
+    }
+}
+
+/// Centrality, communities, brokers and bridges for the user's peers. The
+/// result is cached and only recomputed once the graph has changed.
+pub async fn get_network_analytics(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let mut conn = pool.acquire().await?;
+
+    let graph = graph::load_graph(&mut conn, user_id).await?;
+    let hash = fingerprint(&graph);
+
+    let cached: Option<(Json<NetworkAnalytics>,)> = sqlx::query_as(
+        "SELECT result FROM network_analytics_cache WHERE user_id = $1 AND graph_hash = $2"
+    )
+    .bind(user_id)
+    .bind(&hash)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some((Json(mut analytics),)) = cached {
+        analytics.cached = true;
+        return Ok(HttpResponse::Ok().json(ApiResponse::ok(analytics)));
+    }
+
+    let analytics = analyze(&PeerGraph::from_graph(&graph));
+
+    sqlx::query(
+        "INSERT INTO network_analytics_cache (user_id, graph_hash, result, computed_at)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (user_id) DO UPDATE SET graph_hash = $2, result = $3, computed_at = $4"
+    )
+    .bind(user_id)
+    .bind(&hash)
+    .bind(Json(&analytics))
+    .bind(analytics.computed_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(analytics)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(n: usize, edges: &[(usize, usize)]) -> PeerGraph {
+        let mut adjacency = vec![Vec::new(); n];
+        for &(a, b) in edges {
+            adjacency[a].push((b, 1));
+            adjacency[b].push((a, 1));
+        }
+        PeerGraph {
+            ids: (0..n).map(|i| Uuid::from_u128(i as u128 + 1)).collect(),
+            names: (0..n).map(|i| format!("Peer {}", i)).collect(),
+            adjacency,
+            edges: edges.iter().map(|&(a, b)| (a, b, 1)).collect(),
+        }
+    }
+
+    fn articulation_points(cuts: &Cuts) -> Vec<usize> {
+        (0..cuts.articulation.len()).filter(|&v| cuts.articulation[v]).collect()
+    }
+
+    #[test]
+    fn betweenness_on_a_path_peaks_in_the_middle() {
+        let g = graph(4, &[(0, 1), (1, 2), (2, 3)]);
+        assert_eq!(betweenness(&g), vec![0.0, 0.6667, 0.6667, 0.0]);
+    }
+
+    #[test]
+    fn betweenness_on_a_star_puts_the_hub_on_every_path() {
+        let g = graph(5, &[(0, 1), (0, 2), (0, 3), (0, 4)]);
+        assert_eq!(betweenness(&g), vec![1.0, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn triangles_joined_by_one_edge_split_at_the_join() {
+        let g = graph(6, &[(0, 1), (1, 2), (0, 2), (3, 4), (4, 5), (3, 5), (2, 3)]);
+
+        let community = detect_communities(&g);
+        assert_eq!(community, vec![0, 0, 0, 1, 1, 1]);
+
+        let cuts = Cuts::find(&g);
+        assert_eq!(cuts.bridges, vec![(2, 3, 1)]);
+        assert_eq!(articulation_points(&cuts), vec![2, 3]);
+    }
+
+    #[test]
+    fn cuts_survive_a_long_chain() {
+        let n = 200_000;
+        let edges: Vec<(usize, usize)> = (1..n).map(|v| (v - 1, v)).collect();
+        let g = graph(n, &edges);
+        let cuts = Cuts::find(&g);
+
+        assert_eq!(cuts.bridges.len(), n - 1);
+        assert_eq!(articulation_points(&cuts).len(), n - 2);
+    }
+}
@@ -0,0 +1,223 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::PageMeta;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+/// A column (or expression) a list endpoint can be ordered by. `sql_type` is
+/// what the cursor's text value is cast back to when resuming a page.
+pub struct SortKey {
+    pub name: &'static str,
+    pub expr: &'static str,
+    pub sql_type: &'static str,
+    pub default_desc: bool,
+}
+
+/// Opaque position in a sorted list: the sort value and id of the last row
+/// on the previous page. Tied to the sort it was issued for.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    desc: bool,
+    value: String,
+    id: Uuid,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    /// Decodes a cursor issued for `key`. The sort value is checked against
+    /// the key's type here so a tampered cursor can't reach the SQL cast.
+    fn decode(s: &str, key: &SortKey) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(s).ok()?;
+        let cursor: Self = serde_json::from_slice(&bytes).ok()?;
+        let valid = match key.sql_type {
+            "int" => cursor.value.parse::<i32>().is_ok(),
+            "int[]" => cursor
+                .value
+                .strip_prefix('{')
+                .and_then(|v| v.strip_suffix('}'))
+                .is_some_and(|v| v.split(',').all(|n| n.parse::<i32>().is_ok())),
+            "timestamptz" => DateTime::parse_from_str(&cursor.value, "%Y-%m-%d %H:%M:%S%.f%#z").is_ok(),
+            _ => true,
+        };
+        valid.then_some(cursor)
+    }
+}
+
+/// A row plus its sort value rendered as text, as selected by `Page::select`.
+#[derive(FromRow)]
+pub struct Keyed<T> {
+    #[sqlx(flatten)]
+    pub item: T,
+    pub sort_value: String,
+}
+
+pub struct Page {
+    key: &'static SortKey,
+    desc: bool,
+    limit: i64,
+    cursor: Option<Cursor>,
+}
+
+impl Page {
+    /// Validates the caller's `sort`, `order`, `limit` and `cursor` against
+    /// the keys an endpoint supports. The first key is the default.
+    pub fn new(
+        keys: &'static [SortKey],
+        sort: Option<&str>,
+        order: Option<&str>,
+        limit: Option<i64>,
+        cursor: Option<&str>,
+    ) -> Result<Self, AppError> {
+        let key = match sort {
+            None => &keys[0],
+            Some(name) => keys.iter().find(|k| k.name == name).ok_or_else(|| {
+                let names: Vec<&str> = keys.iter().map(|k| k.name).collect();
+                AppError::invalid("sort", format!("Unknown sort key. Use one of: {}", names.join(", ")))
+            })?,
+        };
+
+        let desc = match order {
+            None => key.default_desc,
+            Some("asc") => false,
+            Some("desc") => true,
+            Some(_) => return Err(AppError::invalid("order", "Order must be asc or desc")),
+        };
+
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(AppError::invalid("limit", format!("Limit must be between 1 and {}", MAX_PAGE_SIZE)));
+        }
+
+        let cursor = match cursor {
+            None => None,
+            Some(s) => {
+                let c = Cursor::decode(s, key).ok_or_else(|| AppError::invalid("cursor", "Invalid cursor"))?;
+                if c.sort != key.name || c.desc != desc {
+                    return Err(AppError::invalid("cursor", "Cursor was issued for a different sort order"));
+                }
+                Some(c)
+            }
+        };
+
+        Ok(Self { key, desc, limit, cursor })
+    }
+
+    /// Pushes `SELECT <columns>, <sort expr>::text AS sort_value` to start a
+    /// page query.
+    pub fn select(&self, qb: &mut QueryBuilder<'_, Postgres>, columns: &str) {
+        qb.push("SELECT ")
+            .push(columns)
+            .push(", (")
+            .push(self.key.expr)
+            .push(")::text AS sort_value");
+    }
+
+    /// Pushes the keyset condition (as an `AND` clause) followed by the
+    /// `ORDER BY` and `LIMIT`. `id_column` breaks ties between equal sort
+    /// values.
+    pub fn push_bounds(&self, qb: &mut QueryBuilder<'_, Postgres>, id_column: &str) {
+        let dir = if self.desc { "DESC" } else { "ASC" };
+
+        if let Some(cursor) = &self.cursor {
+            qb.push(" AND ((")
+                .push(self.key.expr)
+                .push("), ")
+                .push(id_column)
+                .push(if self.desc { ") < (" } else { ") > (" })
+                .push_bind(cursor.value.clone())
+                .push("::")
+                .push(self.key.sql_type)
+                .push(", ")
+                .push_bind(cursor.id)
+                .push(")");
+        }
+
+        qb.push(" ORDER BY (")
+            .push(self.key.expr)
+            .push(") ")
+            .push(dir)
+            .push(", ")
+            .push(id_column)
+            .push(" ")
+            .push(dir)
+            .push(" LIMIT ")
+            .push_bind(self.limit + 1);
+    }
+
+    /// Trims the extra row fetched by `push_bounds` and builds the cursor for
+    /// the next page from the last row kept.
+    pub fn finish<T>(&self, mut rows: Vec<Keyed<T>>, total: i64, id: impl Fn(&T) -> Uuid) -> (Vec<T>, PageMeta) {
+        let has_more = rows.len() as i64 > self.limit;
+        rows.truncate(self.limit as usize);
+
+        let next_cursor = if has_more {
+            rows.last().map(|last| {
+                Cursor {
+                    sort: self.key.name.to_string(),
+                    desc: self.desc,
+                    value: last.sort_value.clone(),
+                    id: id(&last.item),
+                }
+                .encode()
+            })
+        } else {
+            None
+        };
+
+        let items = rows.into_iter().map(|r| r.item).collect();
+        (items, PageMeta { next_cursor, total })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: &[SortKey] = &[
+        SortKey { name: "created_at", expr: "created_at", sql_type: "timestamptz", default_desc: true },
+        SortKey { name: "points", expr: "points", sql_type: "int", default_desc: true },
+        SortKey { name: "urgency", expr: "ARRAY[rank, -days]", sql_type: "int[]", default_desc: true },
+    ];
+
+    fn cursor(sort: &str, value: &str) -> String {
+        Cursor { sort: sort.to_string(), desc: true, value: value.to_string(), id: Uuid::new_v4() }.encode()
+    }
+
+    fn page(sort: &str, cursor: &str) -> Result<Page, AppError> {
+        Page::new(KEYS, Some(sort), None, None, Some(cursor))
+    }
+
+    #[test]
+    fn accepts_cursors_in_the_form_postgres_renders_them() {
+        assert!(page("created_at", &cursor("created_at", "2026-10-18 10:20:40.350507+00")).is_ok());
+        assert!(page("created_at", &cursor("created_at", "2026-10-18 10:20:40+05:30")).is_ok());
+        assert!(page("points", &cursor("points", "-15")).is_ok());
+        assert!(page("urgency", &cursor("urgency", "{1,-120}")).is_ok());
+    }
+
+    #[test]
+    fn rejects_cursors_whose_value_does_not_fit_the_sort() {
+        assert!(page("created_at", &cursor("created_at", "yesterday")).is_err());
+        assert!(page("points", &cursor("points", "1.5")).is_err());
+        assert!(page("points", &cursor("points", "99999999999")).is_err());
+        assert!(page("urgency", &cursor("urgency", "{1,-120")).is_err());
+        assert!(page("urgency", &cursor("urgency", "{1,x}")).is_err());
+    }
+
+    #[test]
+    fn rejects_cursors_with_a_malformed_id() {
+        let json = r#"{"sort":"points","desc":true,"value":"3","id":"not-a-uuid"}"#;
+        assert!(page("points", &URL_SAFE_NO_PAD.encode(json)).is_err());
+    }
+}
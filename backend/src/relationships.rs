@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::AppError;
+use crate::layout;
+use crate::models::*;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+const BASE_SCORE: i32 = 50;
+const SHARED_COMPLETED_POINTS: i32 = 8;
+const SHARED_STALLED_PENALTY: i32 = 5;
+const SHARED_CRITICAL_PENALTY: i32 = 10;
+const AGREEMENT_ACCEPTED_POINTS: i32 = 6;
+const AGREEMENT_REJECTED_PENALTY: i32 = 4;
+/// Interactions beyond this many stop adding to the score.
+const INTERACTION_CAP: i32 = 50;
+const POINTS_PER_INTERACTION: f64 = 0.4;
+
+const HIGH_THRESHOLD: i32 = 70;
+const MEDIUM_THRESHOLD: i32 = 40;
+
+fn recency_points(days_since_interaction: i32) -> i32 {
+    match days_since_interaction {
+        d if d <= 7 => 10,
+        d if d <= 30 => 5,
+        d if d <= 90 => 0,
+        _ => -10,
+    }
+}
+
+pub fn trust_level_for(score: i32) -> &'static str {
+    if score >= HIGH_THRESHOLD {
+        "High"
+    } else if score >= MEDIUM_THRESHOLD {
+        "Medium"
+    } else {
+        "Low"
+    }
+}
+
+/// Scores a relationship from 0 to 100, starting from a neutral 50.
+pub fn compute_relationship_score(factors: RelationshipFactors) -> RelationshipScore {
+    let interaction_points = (factors.interactions.clamp(0, INTERACTION_CAP) as f64 * POINTS_PER_INTERACTION).round() as i32;
+
+    let breakdown = vec![
+        RelationshipComponent { factor: "base", points: BASE_SCORE },
+        RelationshipComponent { factor: "shared_completed", points: factors.shared_completed * SHARED_COMPLETED_POINTS },
+        RelationshipComponent { factor: "shared_stalled", points: -factors.shared_stalled * SHARED_STALLED_PENALTY },
+        RelationshipComponent { factor: "shared_critical", points: -factors.shared_critical * SHARED_CRITICAL_PENALTY },
+        RelationshipComponent { factor: "interaction_frequency", points: interaction_points },
+        RelationshipComponent { factor: "interaction_recency", points: recency_points(factors.days_since_interaction) },
+        RelationshipComponent { factor: "agreements_accepted", points: factors.agreements_accepted * AGREEMENT_ACCEPTED_POINTS },
+        RelationshipComponent { factor: "agreements_rejected", points: -factors.agreements_rejected * AGREEMENT_REJECTED_PENALTY },
+    ];
+
+    let score = breakdown.iter().map(|c| c.points).sum::<i32>().clamp(0, 100);
+
+    RelationshipScore {
+        score,
+        trust_level: trust_level_for(score).to_string(),
+        factors,
+        breakdown,
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct PeerFactors {
+    peer_id: Uuid,
+    #[sqlx(flatten)]
+    factors: RelationshipFactors,
+}
+
+/// Relationship factors for one user's peers (or everyone's when `user_id`
+/// is `None`), optionally narrowed to a single peer.
+async fn load_factors(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+    peer_id: Option<Uuid>,
+) -> Result<Vec<PeerFactors>, sqlx::Error> {
+    sqlx::query_as::<_, PeerFactors>(
+        "SELECT np.id AS peer_id,
+                (COUNT(DISTINCT r.id) FILTER (WHERE r.status = 'completed'))::int AS shared_completed,
+                (COUNT(DISTINCT r.id) FILTER (WHERE r.status = 'stalled'))::int AS shared_stalled,
+                (COUNT(DISTINCT r.id) FILTER (WHERE r.status = 'critical'))::int AS shared_critical,
+                (COUNT(DISTINCT r.id) FILTER (WHERE r.status = 'fair'))::int AS shared_open,
+                np.interactions,
+                GREATEST(0, EXTRACT(DAY FROM NOW() - np.last_interaction))::int AS days_since_interaction,
+                COALESCE(ag.accepted, 0)::int AS agreements_accepted,
+                COALESCE(ag.rejected, 0)::int AS agreements_rejected
+         FROM network_peers np
+         LEFT JOIN (requests r JOIN request_peers rp ON rp.request_id = r.id)
+                ON r.user_id = np.user_id AND r.deleted_at IS NULL AND rp.peer_name = np.peer_name
+         LEFT JOIN LATERAL (
+             SELECT COUNT(*) FILTER (WHERE a.status = 'accepted') AS accepted,
+                    COUNT(*) FILTER (WHERE a.status = 'rejected') AS rejected
+             FROM agreements a
+             JOIN requests ar ON ar.id = a.request_id
+             WHERE ar.user_id = np.user_id AND ar.deleted_at IS NULL AND a.peer_name = np.peer_name
+         ) ag ON TRUE
+         WHERE ($1::uuid IS NULL OR np.user_id = $1) AND ($2::uuid IS NULL OR np.id = $2)
+         GROUP BY np.id, ag.accepted, ag.rejected"
+    )
+    .bind(user_id)
+    .bind(peer_id)
+    .fetch_all(conn)
+    .await
+}
+
+async fn store_scores(conn: &mut PgConnection, scored: &[(Uuid, &RelationshipScore)]) -> Result<(), sqlx::Error> {
+    let ids: Vec<Uuid> = scored.iter().map(|(id, _)| *id).collect();
+    let scores: Vec<i32> = scored.iter().map(|(_, s)| s.score).collect();
+    let levels: Vec<&str> = scored.iter().map(|(_, s)| s.trust_level.as_str()).collect();
+
+    sqlx::query(
+        "UPDATE network_peers np SET relationship_score = s.score, trust_level = s.level
+         FROM UNNEST($1::uuid[], $2::int[], $3::text[]) AS s(id, score, level)
+         WHERE np.id = s.id
+           AND (np.relationship_score IS DISTINCT FROM s.score OR np.trust_level <> s.level)"
+    )
+    .bind(&ids)
+    .bind(&scores)
+    .bind(&levels)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Rescores every relationship `user_id` has, or every relationship in the
+/// system when `user_id` is `None`, and updates each peer's trust level to
+/// match. For a single user the network map is nudged to suit, too. Call it
+/// after anything that changes a relationship's factors.
+pub async fn refresh_relationship_scores(conn: &mut PgConnection, user_id: Option<Uuid>) -> Result<usize, sqlx::Error> {
+    let scored: Vec<(Uuid, RelationshipScore)> = load_factors(conn, user_id, None)
+        .await?
+        .into_iter()
+        .map(|p| (p.peer_id, compute_relationship_score(p.factors)))
+        .collect();
+
+    let refs: Vec<(Uuid, &RelationshipScore)> = scored.iter().map(|(id, score)| (*id, score)).collect();
+    store_scores(conn, &refs).await?;
+
+    if let Some(user_id) = user_id {
+        layout::refresh_layout(conn, user_id, layout::default_seed(user_id), true).await?;
+    }
+    Ok(scored.len())
+}
+
+/// Rescores all relationships once per `REFRESH_INTERVAL`, which is what
+/// keeps the recency factor current for peers nobody touches.
+pub fn spawn_refresh_job(pool: PgPool) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let result = async {
+                let mut conn = pool.acquire().await?;
+                refresh_relationship_scores(&mut conn, None).await
+            }
+            .await;
+            if let Err(e) = result {
+                eprintln!("Relationship score refresh failed: {}", e);
+            }
+        }
+    });
+}
+
+pub async fn get_network_peer(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let peer_id = path.into_inner();
+    let mut tx = pool.begin().await?;
+
+    let factors = load_factors(&mut tx, Some(user.id), Some(peer_id))
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound("Peer"))?;
+
+    let relationship = compute_relationship_score(factors.factors);
+    store_scores(&mut tx, &[(peer_id, &relationship)]).await?;
+
+    let peer = sqlx::query_as::<_, NetworkPeer>(
+        "SELECT np.*, pts.tier
+         FROM network_peers np
+         LEFT JOIN trust_scores pts ON pts.user_id = np.peer_user_id
+         WHERE np.id = $1"
+    )
+    .bind(peer_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(NetworkPeerDetail { peer, relationship })))
+}
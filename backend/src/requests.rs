@@ -1,128 +1,401 @@
-use actix_web::{web, HttpRequest, HttpResponse};
-use sqlx::PgPool;
+use std::collections::HashMap;
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgExecutor, PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
-use crate::auth::get_user_from_token;
+use crate::agreements::has_accepted_agreement;
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, FieldError};
 use crate::models::*;
+use crate::network::{self, MAX_PEER_NAME_LEN};
+use crate::pagination::{Keyed, Page, SortKey};
+use crate::relationships;
+use crate::trust::{self, NewTrustEvent};
 
-pub async fn list_requests(
-    pool: web::Data<PgPool>,
-    req: HttpRequest,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
+const MAX_TITLE_LEN: usize = 255;
+const MAX_DOCUMENT_ID_LEN: usize = 100;
+
+fn validate_title(title: &str, errors: &mut Vec<FieldError>) {
+    if title.trim().is_empty() {
+        errors.push(FieldError::new("title", "Title is required"));
+    } else if title.trim().chars().count() > MAX_TITLE_LEN {
+        errors.push(FieldError::new("title", format!("Title must be at most {} characters", MAX_TITLE_LEN)));
+    }
+}
 
-    let requests = sqlx::query_as::<_, Request>(
-        "SELECT * FROM requests WHERE user_id = $1 ORDER BY
-         CASE status WHEN 'critical' THEN 0 WHEN 'stalled' THEN 1 ELSE 2 END,
-         stalled_days DESC"
+fn validate_document_id(document_id: Option<&str>, errors: &mut Vec<FieldError>) {
+    if document_id.is_some_and(|d| d.chars().count() > MAX_DOCUMENT_ID_LEN) {
+        errors.push(FieldError::new(
+            "document_id",
+            format!("Document ID must be at most {} characters", MAX_DOCUMENT_ID_LEN),
+        ));
+    }
+}
+
+/// Trims peer names and drops duplicates, keeping the caller's order.
+fn normalize_peers(peers: &[String], errors: &mut Vec<FieldError>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for peer in peers {
+        let name = peer.trim();
+        if name.is_empty() {
+            errors.push(FieldError::new("peers", "Peer names must not be empty"));
+        } else if name.chars().count() > MAX_PEER_NAME_LEN {
+            errors.push(FieldError::new(
+                "peers",
+                format!("Peer names must be at most {} characters", MAX_PEER_NAME_LEN),
+            ));
+        } else if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+async fn fetch_owned_request(
+    conn: impl PgExecutor<'_>,
+    user_id: Uuid,
+    request_id: Uuid,
+) -> Result<Request, AppError> {
+    sqlx::query_as::<_, Request>(
+        "SELECT * FROM requests WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL"
     )
+    .bind(request_id)
     .bind(user_id)
-    .fetch_all(pool.get_ref())
-    .await;
-
-    match requests {
-        Ok(reqs) => {
-            let mut result = Vec::new();
-            for r in reqs {
-                let peers = sqlx::query_as::<_, RequestPeer>(
-                    "SELECT * FROM request_peers WHERE request_id = $1"
-                )
-                .bind(r.id)
-                .fetch_all(pool.get_ref())
-                .await
-                .unwrap_or_default();
-
-                result.push(RequestWithPeers {
-                    id: r.id,
-                    user_id: r.user_id,
-                    title: r.title,
-                    description: r.description,
-                    status: r.status,
-                    stalled_days: r.stalled_days,
-                    document_id: r.document_id,
-                    peers: peers.into_iter().map(|p| p.peer_name).collect(),
-                    created_at: r.created_at,
-                    updated_at: r.updated_at,
-                });
-            }
-            HttpResponse::Ok().json(ApiResponse::ok(result))
+    .fetch_optional(conn)
+    .await?
+    .ok_or(AppError::NotFound("Request"))
+}
+
+/// Like `fetch_owned_request`, but also rejects archived requests, which are
+/// read-only until they're unarchived.
+async fn fetch_editable_request(
+    conn: impl PgExecutor<'_>,
+    user_id: Uuid,
+    request_id: Uuid,
+) -> Result<Request, AppError> {
+    let request = fetch_owned_request(conn, user_id, request_id).await?;
+    if request.archived_at.is_some() {
+        return Err(AppError::Conflict("Archived requests must be unarchived before they can be changed".to_string()));
+    }
+    Ok(request)
+}
+
+async fn fetch_peer_names(conn: impl PgExecutor<'_>, request_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
+    let peers = sqlx::query_as::<_, RequestPeer>(
+        "SELECT * FROM request_peers WHERE request_id = $1 ORDER BY peer_name"
+    )
+    .bind(request_id)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(peers.into_iter().map(|p| p.peer_name).collect())
+}
+
+/// Tags `peers` on a request. The first time a peer is tagged on a request
+/// counts as an interaction with that peer in the owner's network; the caller
+/// rebuilds the owner's trust score once it's done.
+async fn insert_peers(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    request_id: Uuid,
+    peers: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut tagged = Vec::new();
+    for peer in peers {
+        let inserted = sqlx::query(
+            "INSERT INTO request_peers (id, request_id, peer_name) VALUES ($1, $2, $3)
+             ON CONFLICT (request_id, peer_name) DO NOTHING"
+        )
+        .bind(Uuid::new_v4())
+        .bind(request_id)
+        .bind(peer)
+        .execute(&mut *conn)
+        .await?;
+        if inserted.rows_affected() > 0 {
+            tagged.push(peer.clone());
         }
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Failed to fetch requests: {}", e))),
     }
+    network::record_tag_interactions(conn, user_id, request_id, &tagged).await
 }
 
-pub async fn get_request(
-    pool: web::Data<PgPool>,
-    req: HttpRequest,
-    path: web::Path<Uuid>,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
+/// Appends an entry to a request's status history and charges or credits
+/// the owner's trust ledger for it. `changed_by` is `None` for changes made by
+/// the server itself, such as escalation. The caller rebuilds the owner's
+/// trust score once the whole action is recorded.
+pub async fn record_status_change(
+    conn: &mut PgConnection,
+    request: &Request,
+    from_status: Option<RequestStatus>,
+    to_status: RequestStatus,
+    changed_by: Option<Uuid>,
+    reason: Option<&str>,
+    changed_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO request_status_history (id, request_id, from_status, to_status, changed_by, reason, changed_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    )
+    .bind(Uuid::new_v4())
+    .bind(request.id)
+    .bind(from_status)
+    .bind(to_status)
+    .bind(changed_by)
+    .bind(reason)
+    .bind(changed_at)
+    .execute(&mut *conn)
+    .await?;
 
-    let request_id = path.into_inner();
+    for event_type in trust::status_events(from_status, to_status) {
+        let event = NewTrustEvent {
+            event_type: *event_type,
+            quantity: 1,
+            source_type: "request",
+            source_id: Some(request.id),
+            description: &request.title,
+        };
+        trust::append_event(conn, request.user_id, event).await?;
+    }
+
+    relationships::refresh_relationship_scores(conn, Some(request.user_id)).await?;
+
+    Ok(())
+}
 
-    let request = sqlx::query_as::<_, Request>(
-        "SELECT * FROM requests WHERE id = $1 AND user_id = $2"
+/// Refunds the stalled and critical penalties charged for a request when it's
+/// deleted, and charges them again when it's restored.
+async fn settle_deleted_penalties(conn: &mut PgConnection, request: &Request, deleted: bool) -> Result<(), sqlx::Error> {
+    let charged: Vec<(TrustEventType, i64, i64)> = sqlx::query_as(
+        "SELECT event_type, COALESCE(SUM(quantity) FILTER (WHERE source_type = 'request'), 0), SUM(quantity)
+         FROM trust_events
+         WHERE user_id = $1 AND source_id = $2 AND source_type IN ('request', 'request_deletion')
+           AND event_type IN ('request_stalled', 'request_critical')
+         GROUP BY event_type"
     )
-    .bind(request_id)
-    .bind(user_id)
-    .fetch_optional(pool.get_ref())
-    .await;
-
-    match request {
-        Ok(Some(r)) => {
-            let peers = sqlx::query_as::<_, RequestPeer>(
-                "SELECT * FROM request_peers WHERE request_id = $1"
-            )
-            .bind(r.id)
-            .fetch_all(pool.get_ref())
-            .await
-            .unwrap_or_default();
-
-            HttpResponse::Ok().json(ApiResponse::ok(RequestWithPeers {
-                id: r.id,
-                user_id: r.user_id,
-                title: r.title,
-                description: r.description,
-                status: r.status,
-                stalled_days: r.stalled_days,
-                document_id: r.document_id,
-                peers: peers.into_iter().map(|p| p.peer_name).collect(),
-                created_at: r.created_at,
-                updated_at: r.updated_at,
-            }))
+    .bind(request.user_id)
+    .bind(request.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for (event_type, penalties, net) in charged {
+        let quantity = if deleted { 0 } else { penalties } - net;
+        if quantity == 0 {
+            continue;
         }
-        Ok(None) => HttpResponse::NotFound().json(ApiResponse::<()>::err("Request not found")),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Error: {}", e))),
+        let event = NewTrustEvent {
+            event_type,
+            quantity: quantity as i32,
+            source_type: "request_deletion",
+            source_id: Some(request.id),
+            description: &request.title,
+        };
+        trust::append_event(conn, request.user_id, event).await?;
     }
+
+    Ok(())
+}
+
+/// Pairs each request with its peers, loading the peers of all of them in a
+/// single query so listing cost doesn't grow with the number of requests.
+/// Preserves the order of `requests`.
+async fn attach_peers(
+    conn: impl PgExecutor<'_>,
+    requests: Vec<Request>,
+) -> Result<Vec<RequestWithPeers>, sqlx::Error> {
+    if requests.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ids: Vec<Uuid> = requests.iter().map(|r| r.id).collect();
+    let peers = sqlx::query_as::<_, RequestPeer>(
+        "SELECT * FROM request_peers WHERE request_id = ANY($1) ORDER BY peer_name"
+    )
+    .bind(&ids)
+    .fetch_all(conn)
+    .await?;
+
+    let mut by_request: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for peer in peers {
+        by_request.entry(peer.request_id).or_default().push(peer.peer_name);
+    }
+
+    Ok(requests
+        .into_iter()
+        .map(|r| {
+            let peers = by_request.remove(&r.id).unwrap_or_default();
+            with_peers(r, peers)
+        })
+        .collect())
+}
+
+async fn attach_peers_one(conn: impl PgExecutor<'_>, request: Request) -> Result<RequestWithPeers, sqlx::Error> {
+    let mut result = attach_peers(conn, vec![request]).await?;
+    Ok(result.remove(0))
+}
+
+fn with_peers(r: Request, peers: Vec<String>) -> RequestWithPeers {
+    RequestWithPeers {
+        id: r.id,
+        user_id: r.user_id,
+        title: r.title,
+        description: r.description,
+        status: r.status,
+        stalled_days: r.stalled_days,
+        document_id: r.document_id,
+        peers,
+        archived_at: r.archived_at,
+        created_at: r.created_at,
+        updated_at: r.updated_at,
+    }
+}
+
+const REQUEST_SORT_KEYS: &[SortKey] = &[
+    // Critical first, then stalled, then everything else; longest-stalled
+    // first within each group.
+    SortKey {
+        name: "urgency",
+        expr: "ARRAY[CASE status WHEN 'critical' THEN 0 WHEN 'stalled' THEN 1 ELSE 2 END, -stalled_days]",
+        sql_type: "int[]",
+        default_desc: false,
+    },
+    SortKey { name: "created_at", expr: "created_at", sql_type: "timestamptz", default_desc: true },
+    SortKey { name: "updated_at", expr: "updated_at", sql_type: "timestamptz", default_desc: true },
+    SortKey { name: "stalled_days", expr: "stalled_days", sql_type: "int", default_desc: true },
+    SortKey { name: "title", expr: "title", sql_type: "text", default_desc: false },
+];
+
+fn parse_statuses(raw: Option<&str>) -> Result<Vec<String>, AppError> {
+    let Some(raw) = raw else {
+        return Ok(Vec::new());
+    };
+
+    raw.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<RequestStatus>()
+                .map(|status| status.as_str().to_string())
+                .map_err(|_| AppError::invalid("status", format!("Unknown status '{}'", s)))
+        })
+        .collect()
+}
+
+fn push_request_filters<'a>(
+    qb: &mut QueryBuilder<'a, Postgres>,
+    user_id: Uuid,
+    query: &'a RequestListQuery,
+    statuses: Vec<String>,
+) {
+    qb.push(" FROM requests WHERE user_id = ")
+        .push_bind(user_id)
+        .push(" AND deleted_at IS NULL AND (archived_at IS NOT NULL) = ")
+        .push_bind(query.archived.unwrap_or(false));
+
+    if !statuses.is_empty() {
+        qb.push(" AND status::text = ANY(").push_bind(statuses).push(")");
+    }
+    if let Some(peer) = &query.peer {
+        qb.push(" AND EXISTS (SELECT 1 FROM request_peers rp WHERE rp.request_id = requests.id AND LOWER(rp.peer_name) = LOWER(")
+            .push_bind(peer)
+            .push("))");
+    }
+    if let Some(from) = query.created_from {
+        qb.push(" AND created_at >= ").push_bind(from);
+    }
+    if let Some(to) = query.created_to {
+        qb.push(" AND created_at < ").push_bind(to);
+    }
+    if let Some(from) = query.updated_from {
+        qb.push(" AND updated_at >= ").push_bind(from);
+    }
+    if let Some(to) = query.updated_to {
+        qb.push(" AND updated_at < ").push_bind(to);
+    }
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        qb.push(" AND search_vector @@ websearch_to_tsquery('english', ")
+            .push_bind(q)
+            .push(")");
+    }
+}
+
+/// Loads one page of the caller's requests with their peers. The number of
+/// queries is fixed: one count, one page and one for all the page's peers.
+async fn load_request_page(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    query: &RequestListQuery,
+) -> Result<(Vec<RequestWithPeers>, PageMeta), AppError> {
+    let statuses = parse_statuses(query.status.as_deref())?;
+    let page = Page::new(
+        REQUEST_SORT_KEYS,
+        query.sort.as_deref(),
+        query.order.as_deref(),
+        query.limit,
+        query.cursor.as_deref(),
+    )?;
+
+    let mut count_qb = QueryBuilder::new("SELECT COUNT(*)");
+    push_request_filters(&mut count_qb, user_id, query, statuses.clone());
+    let (total,): (i64,) = count_qb.build_query_as().fetch_one(&mut *conn).await?;
+
+    let mut qb = QueryBuilder::new("");
+    page.select(&mut qb, "*");
+    push_request_filters(&mut qb, user_id, query, statuses);
+    page.push_bounds(&mut qb, "id");
+    let rows: Vec<Keyed<Request>> = qb.build_query_as().fetch_all(&mut *conn).await?;
+    let (requests, meta) = page.finish(rows, total, |r| r.id);
+    let result = attach_peers(&mut *conn, requests).await?;
+
+    Ok((result, meta))
+}
+
+pub async fn list_requests(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<RequestListQuery>,
+) -> Result<HttpResponse, AppError> {
+    let (result, meta) = load_request_page(&mut *pool.acquire().await?, user.id, &query).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::paginated(result, meta)))
+}
+
+pub async fn get_request(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let r = fetch_owned_request(pool.get_ref(), user.id, path.into_inner()).await?;
+    let result = attach_peers_one(pool.get_ref(), r).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(result)))
 }
 
 pub async fn create_request(
     pool: web::Data<PgPool>,
-    req: HttpRequest,
+    user: AuthenticatedUser,
     body: web::Json<CreateRequestBody>,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
 
-    if body.title.trim().is_empty() {
-        return HttpResponse::BadRequest().json(ApiResponse::<()>::err("Title is required"));
+    let mut errors = Vec::new();
+    validate_title(&body.title, &mut errors);
+    validate_document_id(body.document_id.as_deref(), &mut errors);
+    let peer_names = normalize_peers(body.peers.as_deref().unwrap_or_default(), &mut errors);
+    let status = body.status.unwrap_or(RequestStatus::Fair);
+    if status == RequestStatus::Completed {
+        errors.push(FieldError::new("status", "New requests can't start as completed"));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
     }
 
     let request_id = Uuid::new_v4();
 
-    let status = body.status.as_deref().unwrap_or("fair");
-    let valid_statuses = ["fair", "stalled", "critical"];
-    let status = if valid_statuses.contains(&status) { status } else { "fair" };
+    let mut tx = pool.begin().await?;
+    let peer_names = network::canonical_peer_names(&mut tx, user_id, &peer_names).await?;
 
-    let result = sqlx::query_as::<_, Request>(
+    let r = sqlx::query_as::<_, Request>(
         "INSERT INTO requests (id, user_id, title, description, status, document_id) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *"
     )
     .bind(request_id)
@@ -131,74 +404,357 @@ pub async fn create_request(
     .bind(&body.description)
     .bind(status)
     .bind(&body.document_id)
-    .fetch_one(pool.get_ref())
-    .await;
-
-    match result {
-        Ok(r) => {
-            let mut peer_names = Vec::new();
-            if let Some(peers) = &body.peers {
-                for peer in peers {
-                    sqlx::query("INSERT INTO request_peers (id, request_id, peer_name) VALUES ($1, $2, $3)")
-                        .bind(Uuid::new_v4())
-                        .bind(request_id)
-                        .bind(peer)
-                        .execute(pool.get_ref())
-                        .await
-                        .ok();
-                    peer_names.push(peer.clone());
-                }
-            }
+    .fetch_one(&mut *tx)
+    .await?;
 
-            HttpResponse::Ok().json(ApiResponse::ok(RequestWithPeers {
-                id: r.id,
-                user_id: r.user_id,
-                title: r.title,
-                description: r.description,
-                status: r.status,
-                stalled_days: r.stalled_days,
-                document_id: r.document_id,
-                peers: peer_names,
-                created_at: r.created_at,
-                updated_at: r.updated_at,
-            }))
-        }
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Failed to create: {}", e))),
+    insert_peers(&mut tx, user_id, request_id, &peer_names).await?;
+    record_status_change(&mut tx, &r, None, status, Some(user_id), None, Utc::now()).await?;
+    trust::rebuild_trust_score(&mut tx, user_id).await?;
+
+    let result = attach_peers_one(&mut *tx, r).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(result)))
+}
+
+pub async fn update_request(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateRequestBody>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = path.into_inner();
+
+    let mut errors = Vec::new();
+    if let Some(title) = &body.title {
+        validate_title(title, &mut errors);
+    }
+    if let Some(document_id) = &body.document_id {
+        validate_document_id(document_id.as_deref(), &mut errors);
+    }
+    let peer_names = body.peers.as_deref().map(|p| normalize_peers(p, &mut errors));
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let mut tx = pool.begin().await?;
+    let existing = fetch_editable_request(&mut *tx, user.id, request_id).await?;
+
+    let document_id = match &body.document_id {
+        Some(d) => d.clone(),
+        None => existing.document_id,
+    };
+
+    let r = sqlx::query_as::<_, Request>(
+        "UPDATE requests SET title = $1, description = $2, document_id = $3, updated_at = NOW()
+         WHERE id = $4 RETURNING *"
+    )
+    .bind(body.title.as_deref().map(str::trim).unwrap_or(&existing.title))
+    .bind(body.description.as_deref().unwrap_or(&existing.description))
+    .bind(document_id)
+    .bind(request_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if let Some(peer_names) = &peer_names {
+        let peer_names = network::canonical_peer_names(&mut tx, user.id, peer_names).await?;
+        sqlx::query("DELETE FROM request_peers WHERE request_id = $1 AND peer_name <> ALL($2)")
+            .bind(request_id)
+            .bind(&peer_names)
+            .execute(&mut *tx)
+            .await?;
+        insert_peers(&mut tx, user.id, request_id, &peer_names).await?;
+        trust::rebuild_trust_score(&mut tx, user.id).await?;
+        relationships::refresh_relationship_scores(&mut tx, Some(user.id)).await?;
+    }
+
+    let result = attach_peers_one(&mut *tx, r).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(result)))
+}
+
+pub async fn delete_request(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let r = sqlx::query_as::<_, Request>(
+        "UPDATE requests SET deleted_at = NOW() WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL RETURNING *"
+    )
+    .bind(path.into_inner())
+    .bind(user.id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound("Request"))?;
+
+    settle_deleted_penalties(&mut tx, &r, true).await?;
+    relationships::refresh_relationship_scores(&mut tx, Some(user.id)).await?;
+    trust::rebuild_trust_score(&mut tx, user.id).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"deleted": true}))))
+}
+
+pub async fn restore_request(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let r = sqlx::query_as::<_, Request>(
+        "UPDATE requests SET deleted_at = NULL
+         WHERE id = $1 AND user_id = $2 AND deleted_at IS NOT NULL
+         RETURNING *"
+    )
+    .bind(path.into_inner())
+    .bind(user.id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound("Deleted request"))?;
+
+    settle_deleted_penalties(&mut tx, &r, false).await?;
+    relationships::refresh_relationship_scores(&mut tx, Some(user.id)).await?;
+    trust::rebuild_trust_score(&mut tx, user.id).await?;
+
+    let result = attach_peers_one(&mut *tx, r).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(result)))
+}
+
+async fn set_archived(
+    pool: &PgPool,
+    user_id: Uuid,
+    request_id: Uuid,
+    archived: bool,
+) -> Result<HttpResponse, AppError> {
+    let r = sqlx::query_as::<_, Request>(
+        "UPDATE requests SET archived_at = CASE WHEN $3 THEN COALESCE(archived_at, NOW()) END
+         WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+         RETURNING *"
+    )
+    .bind(request_id)
+    .bind(user_id)
+    .bind(archived)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::NotFound("Request"))?;
+
+    let result = attach_peers_one(pool, r).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(result)))
+}
+
+pub async fn archive_request(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    set_archived(pool.get_ref(), user.id, path.into_inner(), true).await
+}
+
+pub async fn unarchive_request(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    set_archived(pool.get_ref(), user.id, path.into_inner(), false).await
+}
+
+pub async fn add_request_peers(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    body: web::Json<RequestPeersBody>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = path.into_inner();
+
+    let mut errors = Vec::new();
+    let peer_names = normalize_peers(&body.peers, &mut errors);
+    if peer_names.is_empty() && errors.is_empty() {
+        errors.push(FieldError::new("peers", "At least one peer is required"));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let mut tx = pool.begin().await?;
+    fetch_editable_request(&mut *tx, user.id, request_id).await?;
+
+    let peer_names = network::canonical_peer_names(&mut tx, user.id, &peer_names).await?;
+    insert_peers(&mut tx, user.id, request_id, &peer_names).await?;
+    trust::rebuild_trust_score(&mut tx, user.id).await?;
+    sqlx::query("UPDATE requests SET updated_at = NOW() WHERE id = $1")
+        .bind(request_id)
+        .execute(&mut *tx)
+        .await?;
+    relationships::refresh_relationship_scores(&mut tx, Some(user.id)).await?;
+
+    let peers = fetch_peer_names(&mut *tx, request_id).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(peers)))
+}
+
+pub async fn remove_request_peers(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    body: web::Json<RequestPeersBody>,
+) -> Result<HttpResponse, AppError> {
+    let request_id = path.into_inner();
+
+    let mut errors = Vec::new();
+    let peer_names = normalize_peers(&body.peers, &mut errors);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
     }
+
+    let mut tx = pool.begin().await?;
+    fetch_editable_request(&mut *tx, user.id, request_id).await?;
+
+    let peer_names = network::canonical_peer_names(&mut tx, user.id, &peer_names).await?;
+    sqlx::query("DELETE FROM request_peers WHERE request_id = $1 AND peer_name = ANY($2)")
+        .bind(request_id)
+        .bind(&peer_names)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE requests SET updated_at = NOW() WHERE id = $1")
+        .bind(request_id)
+        .execute(&mut *tx)
+        .await?;
+    relationships::refresh_relationship_scores(&mut tx, Some(user.id)).await?;
+    trust::rebuild_trust_score(&mut tx, user.id).await?;
+
+    let peers = fetch_peer_names(&mut *tx, request_id).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(peers)))
 }
 
 pub async fn update_request_status(
     pool: web::Data<PgPool>,
-    req: HttpRequest,
+    user: AuthenticatedUser,
     path: web::Path<Uuid>,
     body: web::Json<UpdateRequestStatus>,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
-
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
     let request_id = path.into_inner();
-    let valid_statuses = ["fair", "stalled", "critical", "completed"];
+    let reason = body.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+
+    let mut tx = pool.begin().await?;
+    let existing = fetch_editable_request(&mut *tx, user_id, request_id).await?;
+
+    if !existing.status.can_transition_to(body.status) {
+        return Err(AppError::InvalidStatusTransition {
+            from: existing.status,
+            to: body.status,
+        });
+    }
 
-    if !valid_statuses.contains(&body.status.as_str()) {
-        return HttpResponse::BadRequest().json(ApiResponse::<()>::err("Invalid status. Use: fair, stalled, critical, or completed"));
+    if body.status == RequestStatus::Completed && !has_accepted_agreement(&mut *tx, request_id).await? {
+        return Err(AppError::Conflict("A request can't be completed until an agreement has been accepted".to_string()));
     }
 
-    let result = sqlx::query(
-        "UPDATE requests SET status = $1, updated_at = NOW() WHERE id = $2 AND user_id = $3"
+    sqlx::query(
+        "UPDATE requests SET status = $1, updated_at = NOW() WHERE id = $2"
     )
-    .bind(&body.status)
+    .bind(body.status)
     .bind(request_id)
-    .bind(user_id)
-    .execute(pool.get_ref())
-    .await;
+    .execute(&mut *tx)
+    .await?;
+
+    record_status_change(&mut tx, &existing, Some(existing.status), body.status, Some(user_id), reason, Utc::now()).await?;
+    trust::rebuild_trust_score(&mut tx, user_id).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"updated": true}))))
+}
+
+pub async fn get_request_history(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let request = fetch_owned_request(pool.get_ref(), user.id, path.into_inner()).await?;
+
+    let history = sqlx::query_as::<_, RequestStatusChange>(
+        "SELECT * FROM request_status_history WHERE request_id = $1 ORDER BY changed_at, id"
+    )
+    .bind(request.id)
+    .fetch_all(pool.get_ref())
+    .await?;
 
-    match result {
-        Ok(r) if r.rows_affected() > 0 => {
-            HttpResponse::Ok().json(ApiResponse::ok(serde_json::json!({"updated": true})))
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(history)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    /// Table scans the current transaction has made on the request tables.
+    async fn request_table_scans(conn: &mut PgConnection) -> i64 {
+        let (scans,): (i64,) = sqlx::query_as(
+            "SELECT COALESCE(SUM(seq_scan + COALESCE(idx_scan, 0)), 0)::bigint
+             FROM pg_stat_xact_user_tables WHERE relname IN ('requests', 'request_peers')"
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        scans
+    }
+
+    /// Lists `count` requests, each tagged with two peers, and returns the
+    /// scans it took. Runs in a transaction that is rolled back.
+    async fn scans_to_list(pool: &PgPool, count: usize) -> i64 {
+        let user_id = db::create_test_user(pool).await;
+        let mut tx = pool.begin().await.unwrap();
+        for i in 0..count {
+            let request_id = Uuid::new_v4();
+            sqlx::query("INSERT INTO requests (id, user_id, title) VALUES ($1, $2, $3)")
+                .bind(request_id)
+                .bind(user_id)
+                .bind(format!("Request {}", i))
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+            for peer in ["Ada", "Grace"] {
+                sqlx::query("INSERT INTO request_peers (id, request_id, peer_name) VALUES ($1, $2, $3)")
+                    .bind(Uuid::new_v4())
+                    .bind(request_id)
+                    .bind(peer)
+                    .execute(&mut *tx)
+                    .await
+                    .unwrap();
+            }
         }
-        Ok(_) => HttpResponse::NotFound().json(ApiResponse::<()>::err("Request not found")),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Error: {}", e))),
+
+        let query: RequestListQuery = serde_json::from_value(serde_json::json!({"limit": 100})).unwrap();
+        let before = request_table_scans(&mut tx).await;
+        let (page, _) = load_request_page(&mut tx, user_id, &query).await.unwrap();
+        let scans = request_table_scans(&mut tx).await - before;
+        tx.rollback().await.unwrap();
+
+        assert_eq!(page.len(), count);
+        assert!(page.iter().all(|r| r.peers == ["Ada", "Grace"]));
+        scans
+    }
+
+    #[tokio::test]
+    async fn listing_cost_does_not_grow_with_requests() {
+        let Some(pool) = db::test_pool().await else {
+            eprintln!("TEST_DATABASE_URL not set; skipping");
+            return;
+        };
+
+        let one = scans_to_list(&pool, 1).await;
+        let many = scans_to_list(&pool, 40).await;
+        assert_eq!(one, many, "listing 40 requests took {} scans, listing 1 took {}", many, one);
     }
 }
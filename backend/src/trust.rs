@@ -1,31 +1,39 @@
-use actix_web::{web, HttpRequest, HttpResponse};
-use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
 
-use crate::auth::get_user_from_token;
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::auth::{AdminUser, AuthenticatedUser};
+use crate::error::{AppError, FieldError};
 use crate::models::*;
+use crate::pagination::{Keyed, Page, SortKey};
+
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How many buckets the history endpoint averages over.
+const MOVING_AVERAGE_WINDOW: usize = 7;
+const DEFAULT_HISTORY_DAYS: i64 = 90;
 
-pub fn compute_trust_score(
-    completed: i32,
-    stalled: i32,
-    critical: i32,
-    interactions: i32,
-    peer_count: i32,
-) -> TrustScoreComputation {
-    let base = 300;
-    let completed_bonus = completed * 20;
-    let stalled_penalty = stalled * 15;
-    let critical_penalty = critical * 30;
-    let interaction_bonus = (interactions as f64 * 1.5) as i32;
-    let peer_bonus = peer_count * 10;
-
-    let score = (base + completed_bonus + interaction_bonus + peer_bonus
+pub fn compute_trust_score(policy: &ScoringPolicy, factors: TrustFactors) -> TrustScoreComputation {
+    let w = &policy.weights;
+    let completed_bonus = factors.completed_requests * w.completed_points;
+    let stalled_penalty = factors.stalled_requests * w.stalled_penalty;
+    let critical_penalty = factors.critical_requests * w.critical_penalty;
+    let interaction_bonus = (factors.total_interactions as f64 * w.interaction_multiplier) as i32;
+    let peer_bonus = factors.peer_count * w.peer_points;
+
+    #[allow(clippy::manual_clamp)]
+    let score = (w.base_score + completed_bonus + interaction_bonus + peer_bonus + factors.activity_points
+        + factors.decay_points
         - stalled_penalty - critical_penalty)
         .max(0)
         .min(1000);
 
-    let status = if score >= 400 {
+    let status = if score >= w.healthy_threshold {
         "Healthy"
-    } else if score >= 200 {
+    } else if score >= w.fair_threshold {
         "Fair"
     } else {
         "Critical"
@@ -35,127 +43,1207 @@ pub fn compute_trust_score(
     TrustScoreComputation {
         score,
         status,
-        factors: TrustFactors {
-            completed_requests: completed,
-            stalled_requests: stalled,
-            critical_requests: critical,
-            total_interactions: interactions,
-            peer_count,
-        },
+        tier: tier_for_score(w, score),
+        policy_version: policy.version,
+        factors,
+    }
+}
+
+/// The tier a score falls in, ignoring the user's current tier.
+pub fn tier_for_score(weights: &ScoringWeights, score: i32) -> TrustTier {
+    if score >= weights.trusted_threshold {
+        TrustTier::Trusted
+    } else if score >= weights.fair_threshold {
+        TrustTier::Fair
+    } else {
+        TrustTier::Critical
+    }
+}
+
+/// The tier a user currently in `current` should hold at `score`. Promotions
+/// take effect as soon as a threshold is reached; demotions only once the
+/// score is `tier_hysteresis` points below it, so a score hovering around a
+/// boundary doesn't flip the badge on every change.
+pub fn next_tier(weights: &ScoringWeights, current: Option<TrustTier>, score: i32) -> TrustTier {
+    let raw = tier_for_score(weights, score);
+    match current {
+        Some(current) if raw < current => current.min(tier_for_score(weights, score + weights.tier_hysteresis)),
+        _ => raw,
+    }
+}
+
+/// What a ledger event of `quantity` units is worth under `weights`.
+pub fn event_points(weights: &ScoringWeights, event_type: TrustEventType, quantity: i32) -> i32 {
+    match event_type {
+        TrustEventType::RequestCompleted => quantity * weights.completed_points,
+        TrustEventType::RequestStalled => -quantity * weights.stalled_penalty,
+        TrustEventType::RequestCritical => -quantity * weights.critical_penalty,
+        TrustEventType::Interaction => (quantity as f64 * weights.interaction_multiplier) as i32,
+        TrustEventType::PeerAdded => quantity * weights.peer_points,
+        // Activity is capped and weighted when it's ingested, so the quantity
+        // already is the points.
+        TrustEventType::Activity => quantity,
+        TrustEventType::Decay => quantity,
+        TrustEventType::Repricing => quantity,
+    }
+}
+
+/// What an event is worth when the user's earlier interactions add up to
+/// `previous_interactions`. Interactions are priced on the running total, so
+/// however they're split into events their points add up to the score's
+/// interaction bonus.
+fn price_event(weights: &ScoringWeights, event_type: TrustEventType, quantity: i32, previous_interactions: i32) -> i32 {
+    match event_type {
+        TrustEventType::Interaction => {
+            event_points(weights, event_type, previous_interactions + quantity)
+                - event_points(weights, event_type, previous_interactions)
+        }
+        _ => event_points(weights, event_type, quantity),
+    }
+}
+
+pub async fn active_policy(conn: &mut PgConnection) -> Result<ScoringPolicy, sqlx::Error> {
+    sqlx::query_as::<_, ScoringPolicy>("SELECT * FROM scoring_policies WHERE is_active")
+        .fetch_one(conn)
+        .await
+}
+
+/// The ledger events a request status change produces. Penalties are
+/// charged when a request gets worse and are not refunded when it recovers,
+/// so moving a request back and forth can't inflate the score. Skipping
+/// straight to critical charges the stalled penalty too, so the total doesn't
+/// depend on whether anyone saw the request while it was stalled.
+pub fn status_events(from: Option<RequestStatus>, to: RequestStatus) -> &'static [TrustEventType] {
+    match (from, to) {
+        (_, RequestStatus::Completed) => &[TrustEventType::RequestCompleted],
+        (Some(RequestStatus::Critical), RequestStatus::Critical) => &[],
+        (Some(RequestStatus::Stalled), RequestStatus::Critical) => &[TrustEventType::RequestCritical],
+        (_, RequestStatus::Critical) => &[TrustEventType::RequestStalled, TrustEventType::RequestCritical],
+        (None | Some(RequestStatus::Fair), RequestStatus::Stalled) => &[TrustEventType::RequestStalled],
+        _ => &[],
+    }
+}
+
+pub struct NewTrustEvent<'a> {
+    pub event_type: TrustEventType,
+    pub quantity: i32,
+    pub source_type: &'static str,
+    pub source_id: Option<Uuid>,
+    pub description: &'a str,
+}
+
+/// Appends `event` to the user's ledger and refreshes their score
+/// projection. Run it in the same transaction as the action it records.
+/// Actions that record several events append them with `append_event` and
+/// rebuild once at the end instead.
+pub async fn record_event(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    event: NewTrustEvent<'_>,
+) -> Result<TrustScoreComputation, sqlx::Error> {
+    insert_event(conn, user_id, None, &event).await?;
+    rebuild_with_source(conn, user_id, event_source(event.event_type)).await
+}
+
+/// Appends `event` to the user's ledger without refreshing the projection;
+/// the caller rebuilds the score once the action has recorded everything.
+pub async fn append_event(conn: &mut PgConnection, user_id: Uuid, event: NewTrustEvent<'_>) -> Result<(), sqlx::Error> {
+    insert_event(conn, user_id, None, &event).await?;
+    Ok(())
+}
+
+/// Like `append_event`, but credits an event only once per `key`. Returns
+/// whether the event was recorded; repeats leave the ledger as it is.
+pub async fn append_event_once(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    key: &str,
+    event: NewTrustEvent<'_>,
+) -> Result<bool, sqlx::Error> {
+    insert_event(conn, user_id, Some(key), &event).await
+}
+
+fn event_source(event_type: TrustEventType) -> &'static str {
+    match event_type {
+        TrustEventType::Decay => "decay",
+        _ => "recalculation",
+    }
+}
+
+async fn insert_event(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    key: Option<&str>,
+    event: &NewTrustEvent<'_>,
+) -> Result<bool, sqlx::Error> {
+    let policy = active_policy(conn).await?;
+    let previous_interactions = if event.event_type == TrustEventType::Interaction {
+        let (total,): (i64,) = sqlx::query_as(
+            "SELECT COALESCE(SUM(quantity), 0) FROM trust_events WHERE user_id = $1 AND event_type = 'interaction'"
+        )
+        .bind(user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        total as i32
+    } else {
+        0
+    };
+
+    let inserted = sqlx::query(
+        "INSERT INTO trust_events (id, user_id, event_type, quantity, points, source_type, source_id, description, ledger_key)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         ON CONFLICT (user_id, ledger_key) WHERE ledger_key IS NOT NULL DO NOTHING"
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(event.event_type)
+    .bind(event.quantity)
+    .bind(price_event(&policy.weights, event.event_type, event.quantity, previous_interactions))
+    .bind(event.source_type)
+    .bind(event.source_id)
+    .bind(event.description)
+    .bind(key)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(inserted.rows_affected() > 0)
+}
+
+/// A user's ledger summed per event type, next to the score currently stored
+/// for them.
+#[derive(Debug, FromRow)]
+struct LedgerTotals {
+    user_id: Uuid,
+    username: String,
+    current_score: Option<i32>,
+    current_status: Option<String>,
+    current_tier: Option<TrustTier>,
+    points: i64,
+    completed: i64,
+    stalled: i64,
+    critical: i64,
+    interactions: i64,
+    peers: i64,
+    activity: i64,
+    decay: i64,
+    days_inactive: i32,
+}
+
+impl LedgerTotals {
+    fn factors(&self) -> TrustFactors {
+        TrustFactors {
+            completed_requests: self.completed as i32,
+            stalled_requests: self.stalled as i32,
+            critical_requests: self.critical as i32,
+            total_interactions: self.interactions as i32,
+            peer_count: self.peers as i32,
+            activity_points: self.activity as i32,
+            decay_points: self.decay as i32,
+            days_inactive: self.days_inactive,
+        }
+    }
+}
+
+/// Ledger totals for one user, or for every user when `user_id` is `None`.
+async fn ledger_totals(conn: &mut PgConnection, user_id: Option<Uuid>) -> Result<Vec<LedgerTotals>, sqlx::Error> {
+    sqlx::query_as::<_, LedgerTotals>(
+        "SELECT u.id AS user_id, u.username,
+                ts.score AS current_score, ts.status AS current_status, ts.tier AS current_tier,
+                COALESCE(SUM(e.points), 0) AS points,
+                COALESCE(SUM(e.quantity) FILTER (WHERE e.event_type = 'request_completed'), 0) AS completed,
+                COALESCE(SUM(e.quantity) FILTER (WHERE e.event_type = 'request_stalled'), 0) AS stalled,
+                COALESCE(SUM(e.quantity) FILTER (WHERE e.event_type = 'request_critical'), 0) AS critical,
+                COALESCE(SUM(e.quantity) FILTER (WHERE e.event_type = 'interaction'), 0) AS interactions,
+                COALESCE(SUM(e.quantity) FILTER (WHERE e.event_type = 'peer_added'), 0) AS peers,
+                COALESCE(SUM(e.quantity) FILTER (WHERE e.event_type = 'activity'), 0) AS activity,
+                COALESCE(SUM(e.quantity) FILTER (WHERE e.event_type = 'decay'), 0) AS decay,
+                FLOOR(EXTRACT(EPOCH FROM NOW() - COALESCE(
+                    MAX(e.created_at) FILTER (
+                        WHERE e.event_type NOT IN ('decay', 'repricing', 'request_stalled', 'request_critical')
+                    ),
+                    u.created_at
+                )) / 86400)::int AS days_inactive
+         FROM users u
+         LEFT JOIN trust_events e ON e.user_id = u.id
+         LEFT JOIN trust_scores ts ON ts.user_id = u.id
+         WHERE $1::uuid IS NULL OR u.id = $1
+         GROUP BY u.id, u.username, u.created_at, ts.score, ts.status, ts.tier
+         ORDER BY u.username"
+    )
+    .bind(user_id)
+    .fetch_all(conn)
+    .await
+}
+
+/// Stores a freshly computed score and snapshots it, alerting the user when
+/// their tier moves.
+async fn store_score(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    policy: &ScoringPolicy,
+    mut computation: TrustScoreComputation,
+    source: &str,
+) -> Result<TrustScoreComputation, sqlx::Error> {
+    let current: Option<TrustTier> = sqlx::query_scalar("SELECT tier FROM trust_scores WHERE user_id = $1 FOR UPDATE")
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    computation.tier = next_tier(&policy.weights, current, computation.score);
+
+    sqlx::query(
+        "INSERT INTO trust_scores (id, user_id, score, status, policy_version, tier) VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (user_id) DO UPDATE SET score = $3, status = $4, policy_version = $5, tier = $6, updated_at = NOW()"
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(computation.score)
+    .bind(&computation.status)
+    .bind(computation.policy_version)
+    .bind(computation.tier)
+    .execute(&mut *conn)
+    .await?;
+
+    if let Some(previous) = current.filter(|t| *t != computation.tier) {
+        let (title, message) = if computation.tier > previous {
+            (
+                format!("Promoted to {}", computation.tier.label()),
+                format!(
+                    "With a trust score of {}, you moved up from {} to {}.",
+                    computation.score, previous.label(), computation.tier.label()
+                ),
+            )
+        } else {
+            (
+                format!("Moved down to {}", computation.tier.label()),
+                format!(
+                    "With a trust score of {}, you moved down from {} to {}.",
+                    computation.score, previous.label(), computation.tier.label()
+                ),
+            )
+        };
+
+        sqlx::query("INSERT INTO alerts (id, user_id, title, message, alert_type) VALUES ($1, $2, $3, $4, 'trust')")
+            .bind(Uuid::new_v4())
+            .bind(user_id)
+            .bind(title)
+            .bind(message)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sqlx::query(
+        "INSERT INTO trust_score_snapshots (id, user_id, score, status, policy_version, source)
+         VALUES ($1, $2, $3, $4, $5, $6)"
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(computation.score)
+    .bind(&computation.status)
+    .bind(computation.policy_version)
+    .bind(source)
+    .execute(conn)
+    .await?;
+
+    Ok(computation)
+}
+
+/// Recomputes the `trust_scores` row for `user_id` from the ledger under the
+/// active scoring policy.
+pub async fn rebuild_trust_score(conn: &mut PgConnection, user_id: Uuid) -> Result<TrustScoreComputation, sqlx::Error> {
+    rebuild_with_source(conn, user_id, "recalculation").await
+}
+
+async fn rebuild_with_source(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    source: &str,
+) -> Result<TrustScoreComputation, sqlx::Error> {
+    let policy = active_policy(conn).await?;
+    let totals = ledger_totals(conn, Some(user_id)).await?;
+    let factors = totals.first().ok_or(sqlx::Error::RowNotFound)?.factors();
+
+    store_score(conn, user_id, &policy, compute_trust_score(&policy, factors), source).await
+}
+
+/// Rebuilds the scores no policy has produced yet, which is how rolling back
+/// a migration that removed ledger events leaves them.
+pub async fn rebuild_stale_scores(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let stale: Vec<(Uuid,)> = sqlx::query_as("SELECT user_id FROM trust_scores WHERE policy_version IS NULL")
+        .fetch_all(pool)
+        .await?;
+
+    for (user_id,) in &stale {
+        let mut tx = pool.begin().await?;
+        rebuild_trust_score(&mut tx, *user_id).await?;
+        tx.commit().await?;
     }
+
+    Ok(stale.len())
 }
 
 pub async fn get_trust_score(
     pool: web::Data<PgPool>,
-    req: HttpRequest,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
 
     let score = sqlx::query_as::<_, TrustScore>(
         "SELECT * FROM trust_scores WHERE user_id = $1"
     )
     .bind(user_id)
     .fetch_optional(pool.get_ref())
-    .await;
+    .await?
+    .ok_or(AppError::NotFound("Trust score"))?;
 
-    match score {
-        Ok(Some(s)) => HttpResponse::Ok().json(ApiResponse::ok(s)),
-        Ok(None) => HttpResponse::NotFound().json(ApiResponse::<()>::err("No trust score found")),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Error: {}", e))),
-    }
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(score)))
 }
 
 pub async fn recalculate_trust_score(
     pool: web::Data<PgPool>,
-    req: HttpRequest,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let mut tx = pool.begin().await?;
+    let computation = rebuild_trust_score(&mut tx, user.id).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(computation)))
+}
+
+const MAX_SIMULATED_CHANGES: usize = 50;
+const MAX_SIMULATED_COUNT: i32 = 10_000;
+
+fn validate_simulation(body: &SimulationBody) -> Result<(), AppError> {
+    if body.changes.is_empty() {
+        return Err(AppError::invalid("changes", "At least one change is required"));
+    }
+    if body.changes.len() > MAX_SIMULATED_CHANGES {
+        return Err(AppError::invalid(
+            "changes",
+            format!("At most {} changes can be simulated at once", MAX_SIMULATED_CHANGES),
+        ));
+    }
+
+    let errors: Vec<FieldError> = body
+        .changes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, change)| match change {
+            SimulatedChange::AddInteractions { count } | SimulatedChange::AddPeers { count }
+                if !(1..=MAX_SIMULATED_COUNT).contains(count) =>
+            {
+                Some(FieldError::new(
+                    "changes",
+                    format!("Change {}: count must be between 1 and {}", i, MAX_SIMULATED_COUNT),
+                ))
+            }
+            _ => None,
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+fn add_to_factor(factors: &mut TrustFactors, event_type: TrustEventType, quantity: i32) {
+    match event_type {
+        TrustEventType::RequestCompleted => factors.completed_requests += quantity,
+        TrustEventType::RequestStalled => factors.stalled_requests += quantity,
+        TrustEventType::RequestCritical => factors.critical_requests += quantity,
+        TrustEventType::Interaction => factors.total_interactions += quantity,
+        TrustEventType::PeerAdded => factors.peer_count += quantity,
+        TrustEventType::Activity => factors.activity_points += quantity,
+        TrustEventType::Decay => factors.decay_points += quantity,
+        TrustEventType::Repricing => {}
+    }
+}
+
+fn factor_diff(weights: &ScoringWeights, current: &TrustFactors, projected: &TrustFactors) -> Vec<FactorDiff> {
+    [
+        ("completed_requests", TrustEventType::RequestCompleted, current.completed_requests, projected.completed_requests),
+        ("stalled_requests", TrustEventType::RequestStalled, current.stalled_requests, projected.stalled_requests),
+        ("critical_requests", TrustEventType::RequestCritical, current.critical_requests, projected.critical_requests),
+        ("total_interactions", TrustEventType::Interaction, current.total_interactions, projected.total_interactions),
+        ("peer_count", TrustEventType::PeerAdded, current.peer_count, projected.peer_count),
+    ]
+    .into_iter()
+    .filter(|(_, _, cur, proj)| cur != proj)
+    .map(|(factor, event_type, cur, proj)| FactorDiff {
+        factor,
+        current: cur,
+        projected: proj,
+        points: event_points(weights, event_type, proj) - event_points(weights, event_type, cur),
+    })
+    .collect()
+}
+
+/// Applies hypothetical `changes` to `factors` under the same ledger rules as
+/// the real transitions, returning notes about changes that won't move the
+/// score as the user might expect.
+fn apply_changes(
+    factors: &mut TrustFactors,
+    requests: &mut HashMap<Uuid, Request>,
+    agreed: &[Uuid],
+    changes: &[SimulatedChange],
+) -> Result<Vec<String>, AppError> {
+    let mut notes = Vec::new();
+
+    for (i, change) in changes.iter().enumerate() {
+        let (request_id, target) = match change {
+            SimulatedChange::CompleteRequest { request_id } => (*request_id, RequestStatus::Completed),
+            SimulatedChange::ResolveRequest { request_id } => (*request_id, RequestStatus::Fair),
+            SimulatedChange::AddInteractions { count } => {
+                factors.total_interactions += count;
+                continue;
+            }
+            SimulatedChange::AddPeers { count } => {
+                factors.peer_count += count;
+                continue;
+            }
+        };
+
+        let request = requests
+            .get_mut(&request_id)
+            .ok_or_else(|| AppError::invalid("changes", format!("Change {}: request not found", i)))?;
+        if request.archived_at.is_some() {
+            return Err(AppError::invalid("changes", format!("Change {}: \"{}\" is archived", i, request.title)));
+        }
+        if !request.status.can_transition_to(target) {
+            return Err(AppError::invalid(
+                "changes",
+                format!("Change {}: \"{}\" can't move from {} to {}", i, request.title, request.status, target),
+            ));
+        }
+
+        if target == RequestStatus::Completed && !agreed.contains(&request_id) {
+            notes.push(format!("\"{}\" needs an accepted agreement before it can be completed.", request.title));
+        }
+
+        let events = status_events(Some(request.status), target);
+        if events.is_empty() {
+            notes.push(format!(
+                "Moving \"{}\" to {} doesn't change the score; penalties already charged aren't refunded.",
+                request.title, target
+            ));
+        }
+        for event_type in events {
+            add_to_factor(factors, *event_type, 1);
+        }
+        request.status = target;
+    }
+
+    Ok(notes)
+}
+
+/// Projects the user's score after a set of hypothetical changes, applying
+/// the same ledger rules as the real transitions would. Nothing is stored.
+pub async fn simulate_trust_score(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    body: web::Json<SimulationBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    validate_simulation(&body)?;
+
+    let mut conn = pool.acquire().await?;
+    let policy = active_policy(&mut conn).await?;
+    let totals = ledger_totals(&mut conn, Some(user_id))
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound("User"))?;
 
-    let completed: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM requests WHERE user_id = $1 AND status = 'completed'"
+    let request_ids: Vec<Uuid> = body
+        .changes
+        .iter()
+        .filter_map(|c| match c {
+            SimulatedChange::CompleteRequest { request_id } | SimulatedChange::ResolveRequest { request_id } => {
+                Some(*request_id)
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut requests: HashMap<Uuid, Request> = sqlx::query_as::<_, Request>(
+        "SELECT * FROM requests WHERE id = ANY($1) AND user_id = $2 AND deleted_at IS NULL"
     )
+    .bind(&request_ids)
     .bind(user_id)
-    .fetch_one(pool.get_ref())
-    .await
-    .unwrap_or((0,));
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|r| (r.id, r))
+    .collect();
 
-    let stalled: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM requests WHERE user_id = $1 AND status = 'stalled'"
+    let agreed: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT DISTINCT request_id FROM agreements WHERE request_id = ANY($1) AND status = 'accepted'"
     )
-    .bind(user_id)
-    .fetch_one(pool.get_ref())
-    .await
-    .unwrap_or((0,));
+    .bind(&request_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let current_factors = totals.factors();
+    let mut factors = current_factors;
+    let notes = apply_changes(&mut factors, &mut requests, &agreed, &body.changes)?;
+
+    let mut current = compute_trust_score(&policy, current_factors);
+    current.tier = totals.current_tier.unwrap_or(current.tier);
+    let mut projected = compute_trust_score(&policy, factors);
+    projected.tier = next_tier(&policy.weights, totals.current_tier, projected.score);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(SimulationResult {
+        score_change: projected.score - current.score,
+        status_changed: projected.status != current.status,
+        tier_changed: projected.tier != current.tier,
+        factor_diff: factor_diff(&policy.weights, &current_factors, &factors),
+        current,
+        projected,
+        notes,
+    })))
+}
+
+const EVENT_SORT_KEYS: &[SortKey] = &[
+    SortKey { name: "created_at", expr: "created_at", sql_type: "timestamptz", default_desc: true },
+    SortKey { name: "points", expr: "points", sql_type: "int", default_desc: true },
+];
+
+fn push_event_filters<'a>(qb: &mut QueryBuilder<'a, Postgres>, user_id: Uuid, query: &'a TrustEventQuery) {
+    qb.push(" FROM trust_events WHERE user_id = ").push_bind(user_id);
+
+    if let Some(event_type) = query.event_type {
+        qb.push(" AND event_type = ").push_bind(event_type);
+    }
+    if let Some(from) = query.from {
+        qb.push(" AND created_at >= ").push_bind(from);
+    }
+    if let Some(to) = query.to {
+        qb.push(" AND created_at < ").push_bind(to);
+    }
+}
+
+pub async fn list_trust_events(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<TrustEventQuery>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let page = Page::new(
+        EVENT_SORT_KEYS,
+        query.sort.as_deref(),
+        query.order.as_deref(),
+        query.limit,
+        query.cursor.as_deref(),
+    )?;
+
+    let mut count_qb = QueryBuilder::new("SELECT COUNT(*)");
+    push_event_filters(&mut count_qb, user_id, &query);
+    let (total,): (i64,) = count_qb.build_query_as().fetch_one(pool.get_ref()).await?;
+
+    let mut qb = QueryBuilder::new("");
+    page.select(&mut qb, "*");
+    push_event_filters(&mut qb, user_id, &query);
+    page.push_bounds(&mut qb, "id");
+    let rows: Vec<Keyed<TrustEvent>> = qb.build_query_as().fetch_all(pool.get_ref()).await?;
+    let (events, meta) = page.finish(rows, total, |e| e.id);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::paginated(events, meta)))
+}
 
-    let critical: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM requests WHERE user_id = $1 AND status = 'critical'"
+/// Records every user's current score, at most once per user per day, so the
+/// history has a point for days without any recalculation.
+pub async fn take_daily_snapshots(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO trust_score_snapshots (id, user_id, score, status, policy_version, source)
+         SELECT gen_random_uuid(), ts.user_id, ts.score, ts.status, ts.policy_version, 'daily'
+         FROM trust_scores ts
+         WHERE NOT EXISTS (
+             SELECT 1 FROM trust_score_snapshots s
+             WHERE s.user_id = ts.user_id AND s.source = 'daily'
+               AND s.created_at >= date_trunc('day', NOW())
+         )"
     )
-    .bind(user_id)
-    .fetch_one(pool.get_ref())
-    .await
-    .unwrap_or((0,));
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Takes the daily snapshots once per `SNAPSHOT_INTERVAL` for the lifetime of
+/// the server.
+pub fn spawn_snapshot_job(pool: PgPool) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(SNAPSHOT_INTERVAL);
+        loop {
+            interval.tick().await;
+            match take_daily_snapshots(&pool).await {
+                Ok(0) => {}
+                Ok(n) => println!("Trust score snapshots: {} recorded", n),
+                Err(e) => eprintln!("Trust score snapshot job failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Fills in each point's delta from the previous point (or from `baseline`,
+/// the last score before the range) and its trailing moving average, and
+/// collects the points where the status band changed.
+fn annotate_history(points: &mut [TrustScorePoint], baseline: Option<(i32, String)>) -> Vec<StatusTransition> {
+    let mut transitions = Vec::new();
+    let mut previous = baseline;
+
+    for i in 0..points.len() {
+        let window = &points[i.saturating_sub(MOVING_AVERAGE_WINDOW - 1)..=i];
+        let average = window.iter().map(|p| p.score as f64).sum::<f64>() / window.len() as f64;
+
+        let point = &mut points[i];
+        point.moving_average = (average * 100.0).round() / 100.0;
+
+        if let Some((score, status)) = &previous {
+            point.delta = Some(point.score - score);
+            if *status != point.status {
+                transitions.push(StatusTransition {
+                    at: point.bucket_start,
+                    from: status.clone(),
+                    to: point.status.clone(),
+                    score: point.score,
+                });
+            }
+        }
+        previous = Some((point.score, point.status.clone()));
+    }
+
+    transitions
+}
+
+pub async fn get_trust_score_history(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<TrustScoreHistoryQuery>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let bucket = query.bucket.unwrap_or_default();
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - chrono::Duration::days(DEFAULT_HISTORY_DAYS));
 
-    let interactions: (i64,) = sqlx::query_as(
-        "SELECT COALESCE(SUM(interactions), 0) FROM network_peers WHERE user_id = $1"
+    if from >= to {
+        return Err(AppError::invalid("from", "from must be before to"));
+    }
+
+    let mut points = sqlx::query_as::<_, TrustScorePoint>(
+        "SELECT DISTINCT ON (date_trunc($2, created_at))
+                date_trunc($2, created_at) AS bucket_start, score, status, policy_version, source
+         FROM trust_score_snapshots
+         WHERE user_id = $1 AND created_at >= $3 AND created_at < $4
+         ORDER BY date_trunc($2, created_at), created_at DESC"
     )
     .bind(user_id)
-    .fetch_one(pool.get_ref())
-    .await
-    .unwrap_or((0,));
+    .bind(bucket.as_str())
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool.get_ref())
+    .await?;
 
-    let peers: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM network_peers WHERE user_id = $1"
+    let baseline: Option<(i32, String)> = sqlx::query_as(
+        "SELECT score, status FROM trust_score_snapshots
+         WHERE user_id = $1 AND created_at < $2
+         ORDER BY created_at DESC LIMIT 1"
     )
     .bind(user_id)
-    .fetch_one(pool.get_ref())
-    .await
-    .unwrap_or((0,));
+    .bind(from)
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    let transitions = annotate_history(&mut points, baseline);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(TrustScoreHistory {
+        bucket,
+        from,
+        to,
+        moving_average_window: MOVING_AVERAGE_WINDOW,
+        points,
+        transitions,
+    })))
+}
+
+fn validate_weights(weights: &ScoringWeights) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+
+    if !(0..=1000).contains(&weights.base_score) {
+        errors.push(FieldError::new("base_score", "Base score must be between 0 and 1000"));
+    }
+    for (field, value) in [
+        ("completed_points", weights.completed_points),
+        ("stalled_penalty", weights.stalled_penalty),
+        ("critical_penalty", weights.critical_penalty),
+        ("peer_points", weights.peer_points),
+    ] {
+        if value < 0 {
+            errors.push(FieldError::new(field, "Must not be negative"));
+        }
+    }
+    if !weights.interaction_multiplier.is_finite() || weights.interaction_multiplier < 0.0 {
+        errors.push(FieldError::new("interaction_multiplier", "Must be a non-negative number"));
+    }
+    if !(0..=1000).contains(&weights.healthy_threshold) {
+        errors.push(FieldError::new("healthy_threshold", "Threshold must be between 0 and 1000"));
+    }
+    if !(0..=1000).contains(&weights.fair_threshold) {
+        errors.push(FieldError::new("fair_threshold", "Threshold must be between 0 and 1000"));
+    } else if weights.fair_threshold >= weights.healthy_threshold {
+        errors.push(FieldError::new("fair_threshold", "Fair threshold must be below the healthy threshold"));
+    }
+    if !(0..=1000).contains(&weights.trusted_threshold) {
+        errors.push(FieldError::new("trusted_threshold", "Threshold must be between 0 and 1000"));
+    } else if weights.trusted_threshold <= weights.fair_threshold {
+        errors.push(FieldError::new("trusted_threshold", "Trusted threshold must be above the fair threshold"));
+    }
+    if !(0..=100).contains(&weights.tier_hysteresis) {
+        errors.push(FieldError::new("tier_hysteresis", "Hysteresis must be between 0 and 100"));
+    }
+    if !(0..=365).contains(&weights.decay_grace_days) {
+        errors.push(FieldError::new("decay_grace_days", "Must be between 0 and 365"));
+    }
+    if !(0..=3650).contains(&weights.decay_half_life_days) {
+        errors.push(FieldError::new("decay_half_life_days", "Must be between 0 and 3650"));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+pub async fn list_scoring_policies(
+    pool: web::Data<PgPool>,
+    _admin: AdminUser,
+) -> Result<HttpResponse, AppError> {
+    let policies = sqlx::query_as::<_, ScoringPolicy>("SELECT * FROM scoring_policies ORDER BY version DESC")
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(policies)))
+}
+
+/// Scores every user under the proposed weights without storing anything,
+/// so admins can see the effect of a policy before activating it.
+pub async fn preview_scoring_policy(
+    pool: web::Data<PgPool>,
+    _admin: AdminUser,
+    body: web::Json<ScoringWeights>,
+) -> Result<HttpResponse, AppError> {
+    validate_weights(&body)?;
+
+    let mut conn = pool.acquire().await?;
+    let active = active_policy(&mut conn).await?;
+    let totals = ledger_totals(&mut conn, None).await?;
+
+    let proposed = ScoringPolicy {
+        version: active.version + 1,
+        weights: body.into_inner(),
+        is_active: false,
+        created_by: None,
+        created_at: Utc::now(),
+    };
+
+    let entries: Vec<PolicyPreviewEntry> = totals
+        .into_iter()
+        .map(|t| {
+            let projected = compute_trust_score(&proposed, t.factors());
+            PolicyPreviewEntry {
+                projected_tier: next_tier(&proposed.weights, t.current_tier, projected.score),
+                current_tier: t.current_tier,
+                delta: projected.score - t.current_score.unwrap_or(0),
+                user_id: t.user_id,
+                username: t.username,
+                current_score: t.current_score,
+                current_status: t.current_status,
+                projected_score: projected.score,
+                projected_status: projected.status,
+            }
+        })
+        .collect();
+
+    let status_changes = entries
+        .iter()
+        .filter(|e| e.current_status.as_deref() != Some(e.projected_status.as_str()))
+        .count();
+    let average_delta = if entries.is_empty() {
+        0.0
+    } else {
+        entries.iter().map(|e| e.delta as f64).sum::<f64>() / entries.len() as f64
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(PolicyPreview {
+        active_version: active.version,
+        users: entries.len(),
+        status_changes,
+        average_delta,
+        entries,
+    })))
+}
+
+/// Appends a `repricing` event for every user whose ledger points no longer
+/// add up to what their events are worth under `policy`. Recorded events
+/// keep their points.
+async fn append_repricing_events(
+    conn: &mut PgConnection,
+    policy: &ScoringPolicy,
+    totals: &[LedgerTotals],
+) -> Result<(), sqlx::Error> {
+    let mut ids = Vec::new();
+    let mut user_ids = Vec::new();
+    let mut deltas = Vec::new();
+    for t in totals {
+        let worth: i32 = [
+            (TrustEventType::RequestCompleted, t.completed),
+            (TrustEventType::RequestStalled, t.stalled),
+            (TrustEventType::RequestCritical, t.critical),
+            (TrustEventType::Interaction, t.interactions),
+            (TrustEventType::PeerAdded, t.peers),
+            (TrustEventType::Activity, t.activity),
+            (TrustEventType::Decay, t.decay),
+        ]
+        .into_iter()
+        .map(|(event_type, quantity)| event_points(&policy.weights, event_type, quantity as i32))
+        .sum();
 
-    let computation = compute_trust_score(
-        completed.0 as i32,
-        stalled.0 as i32,
-        critical.0 as i32,
-        interactions.0 as i32,
-        peers.0 as i32,
-    );
+        let delta = worth - t.points as i32;
+        if delta != 0 {
+            ids.push(Uuid::new_v4());
+            user_ids.push(t.user_id);
+            deltas.push(delta);
+        }
+    }
 
     sqlx::query(
-        "UPDATE trust_scores SET score = $1, status = $2, updated_at = NOW() WHERE user_id = $3"
+        "INSERT INTO trust_events (id, user_id, event_type, quantity, points, source_type, description)
+         SELECT r.id, r.user_id, 'repricing', r.delta, r.delta, 'scoring_policy', $4
+         FROM UNNEST($1::uuid[], $2::uuid[], $3::int[]) AS r(id, user_id, delta)"
     )
-    .bind(computation.score)
-    .bind(&computation.status)
-    .bind(user_id)
-    .execute(pool.get_ref())
-    .await
-    .ok();
+    .bind(&ids)
+    .bind(&user_ids)
+    .bind(&deltas)
+    .bind(format!("Re-priced under scoring policy v{}", policy.version))
+    .execute(conn)
+    .await?;
 
-    HttpResponse::Ok().json(ApiResponse::ok(computation))
+    Ok(())
 }
 
-pub async fn list_network_peers(
+/// Stores the weights as a new policy version, makes it the active one and
+/// rescores every user under it.
+pub async fn create_scoring_policy(
     pool: web::Data<PgPool>,
-    req: HttpRequest,
-) -> HttpResponse {
-    let user_id = match get_user_from_token(pool.get_ref(), &req).await {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().json(ApiResponse::<()>::err("Not authenticated")),
-    };
+    admin: AdminUser,
+    body: web::Json<ScoringWeights>,
+) -> Result<HttpResponse, AppError> {
+    validate_weights(&body)?;
+
+    let mut tx = pool.begin().await?;
 
-    let peers = sqlx::query_as::<_, NetworkPeer>(
-        "SELECT * FROM network_peers WHERE user_id = $1 ORDER BY interactions DESC"
+    sqlx::query("UPDATE scoring_policies SET is_active = FALSE WHERE is_active")
+        .execute(&mut *tx)
+        .await?;
+
+    let policy = sqlx::query_as::<_, ScoringPolicy>(
+        "INSERT INTO scoring_policies
+            (base_score, completed_points, stalled_penalty, critical_penalty,
+             interaction_multiplier, peer_points, healthy_threshold, fair_threshold,
+             trusted_threshold, tier_hysteresis, decay_grace_days, decay_half_life_days, is_active, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, TRUE, $13)
+         RETURNING *"
     )
-    .bind(user_id)
-    .fetch_all(pool.get_ref())
-    .await;
+    .bind(body.base_score)
+    .bind(body.completed_points)
+    .bind(body.stalled_penalty)
+    .bind(body.critical_penalty)
+    .bind(body.interaction_multiplier)
+    .bind(body.peer_points)
+    .bind(body.healthy_threshold)
+    .bind(body.fair_threshold)
+    .bind(body.trusted_threshold)
+    .bind(body.tier_hysteresis)
+    .bind(body.decay_grace_days)
+    .bind(body.decay_half_life_days)
+    .bind(admin.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let totals = ledger_totals(&mut tx, None).await?;
+    append_repricing_events(&mut tx, &policy, &totals).await?;
+    for t in &totals {
+        store_score(&mut tx, t.user_id, &policy, compute_trust_score(&policy, t.factors()), "recalculation").await?;
+    }
+
+    tx.commit().await?;
+
+    println!("Scoring policy v{} activated; {} user(s) rescored", policy.version, totals.len());
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(PolicyActivation {
+        policy,
+        users_rescored: totals.len(),
+    })))
+}
+
+const PEER_SORT_KEYS: &[SortKey] = &[
+    SortKey { name: "interactions", expr: "np.interactions", sql_type: "int", default_desc: true },
+    SortKey { name: "last_interaction", expr: "np.last_interaction", sql_type: "timestamptz", default_desc: true },
+    SortKey { name: "peer_name", expr: "np.peer_name", sql_type: "text", default_desc: false },
+];
+
+fn push_peer_filters<'a>(qb: &mut QueryBuilder<'a, Postgres>, user_id: Uuid, query: &'a NetworkPeerQuery) {
+    // Peers that are registered users carry their tier.
+    qb.push(
+        " FROM network_peers np
+          LEFT JOIN trust_scores pts ON pts.user_id = np.peer_user_id
+          WHERE np.user_id = ",
+    )
+    .push_bind(user_id);
+
+    if let Some(level) = &query.trust_level {
+        qb.push(" AND np.trust_level = ").push_bind(level);
+    }
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        qb.push(" AND np.peer_name ILIKE ").push_bind(pattern);
+    }
+    if let Some(from) = query.interacted_from {
+        qb.push(" AND np.last_interaction >= ").push_bind(from);
+    }
+    if let Some(to) = query.interacted_to {
+        qb.push(" AND np.last_interaction < ").push_bind(to);
+    }
+}
+
+pub async fn list_network_peers(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<NetworkPeerQuery>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let page = Page::new(
+        PEER_SORT_KEYS,
+        query.sort.as_deref(),
+        query.order.as_deref(),
+        query.limit,
+        query.cursor.as_deref(),
+    )?;
+
+    let mut count_qb = QueryBuilder::new("SELECT COUNT(*)");
+    push_peer_filters(&mut count_qb, user_id, &query);
+    let (total,): (i64,) = count_qb.build_query_as().fetch_one(pool.get_ref()).await?;
+
+    let mut qb = QueryBuilder::new("");
+    page.select(&mut qb, "np.*, pts.tier");
+    push_peer_filters(&mut qb, user_id, &query);
+    page.push_bounds(&mut qb, "np.id");
+    let rows: Vec<Keyed<NetworkPeer>> = qb.build_query_as().fetch_all(pool.get_ref()).await?;
+    let (peers, meta) = page.finish(rows, total, |p| p.id);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::paginated(peers, meta)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weights() -> ScoringWeights {
+        ScoringWeights {
+            base_score: 100,
+            completed_points: 10,
+            stalled_penalty: 15,
+            critical_penalty: 30,
+            interaction_multiplier: 0.5,
+            peer_points: 5,
+            healthy_threshold: 250,
+            fair_threshold: 150,
+            trusted_threshold: 350,
+            tier_hysteresis: 25,
+            decay_grace_days: 14,
+            decay_half_life_days: 90,
+        }
+    }
+
+    #[test]
+    fn tier_for_score_starts_each_tier_at_its_threshold() {
+        let w = weights();
+        assert_eq!(tier_for_score(&w, 149), TrustTier::Critical);
+        assert_eq!(tier_for_score(&w, 150), TrustTier::Fair);
+        assert_eq!(tier_for_score(&w, 349), TrustTier::Fair);
+        assert_eq!(tier_for_score(&w, 350), TrustTier::Trusted);
+    }
+
+    #[test]
+    fn next_tier_promotes_at_the_threshold() {
+        let w = weights();
+        assert_eq!(next_tier(&w, None, 350), TrustTier::Trusted);
+        assert_eq!(next_tier(&w, Some(TrustTier::Fair), 349), TrustTier::Fair);
+        assert_eq!(next_tier(&w, Some(TrustTier::Fair), 350), TrustTier::Trusted);
+        assert_eq!(next_tier(&w, Some(TrustTier::Critical), 350), TrustTier::Trusted);
+    }
+
+    #[test]
+    fn next_tier_demotes_only_past_the_hysteresis_band() {
+        let w = weights();
+        assert_eq!(next_tier(&w, Some(TrustTier::Trusted), 326), TrustTier::Trusted);
+        assert_eq!(next_tier(&w, Some(TrustTier::Trusted), 325), TrustTier::Trusted);
+        assert_eq!(next_tier(&w, Some(TrustTier::Trusted), 324), TrustTier::Fair);
+        assert_eq!(next_tier(&w, Some(TrustTier::Fair), 125), TrustTier::Fair);
+        assert_eq!(next_tier(&w, Some(TrustTier::Fair), 124), TrustTier::Critical);
+    }
+
+    #[test]
+    fn next_tier_drops_through_several_tiers_at_once() {
+        let w = weights();
+        assert_eq!(next_tier(&w, Some(TrustTier::Trusted), 140), TrustTier::Fair);
+        assert_eq!(next_tier(&w, Some(TrustTier::Trusted), 100), TrustTier::Critical);
+    }
+
+    #[test]
+    fn interactions_priced_on_the_running_total_add_up_to_the_bonus() {
+        let w = weights();
+        let bonus = event_points(&w, TrustEventType::Interaction, 7);
+        assert_eq!(bonus, 3);
+
+        for split in [&[7][..], &[1, 1, 1, 1, 1, 1, 1], &[3, 4], &[1, 2, 4]] {
+            let mut previous = 0;
+            let mut total = 0;
+            for &quantity in split {
+                total += price_event(&w, TrustEventType::Interaction, quantity, previous);
+                previous += quantity;
+            }
+            assert_eq!(total, bonus, "split {:?}", split);
+        }
+    }
+
+    #[test]
+    fn other_events_are_priced_on_their_own_quantity() {
+        let w = weights();
+        assert_eq!(price_event(&w, TrustEventType::RequestStalled, 1, 40), -15);
+        assert_eq!(price_event(&w, TrustEventType::RequestCompleted, 2, 40), 20);
+        assert_eq!(price_event(&w, TrustEventType::Activity, 4, 40), 4);
+    }
+
+    #[test]
+    fn status_events_charge_each_penalty_once_per_worsening() {
+        use RequestStatus::*;
+        use TrustEventType::*;
+
+        assert_eq!(status_events(None, Fair), &[]);
+        assert_eq!(status_events(None, Stalled), &[RequestStalled]);
+        assert_eq!(status_events(Some(Fair), Stalled), &[RequestStalled]);
+        assert_eq!(status_events(Some(Fair), Critical), &[RequestStalled, RequestCritical]);
+        assert_eq!(status_events(Some(Stalled), Critical), &[RequestCritical]);
+        assert_eq!(status_events(Some(Critical), Critical), &[]);
+        assert_eq!(status_events(Some(Stalled), Stalled), &[]);
+    }
+
+    #[test]
+    fn status_events_never_refund_a_recovery() {
+        use RequestStatus::*;
+
+        assert_eq!(status_events(Some(Critical), Stalled), &[]);
+        assert_eq!(status_events(Some(Critical), Fair), &[]);
+        assert_eq!(status_events(Some(Stalled), Fair), &[]);
+        assert_eq!(status_events(Some(Critical), Completed), &[TrustEventType::RequestCompleted]);
+    }
+
+    fn request(status: RequestStatus) -> Request {
+        Request {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            title: format!("A {} request", status),
+            description: String::new(),
+            status,
+            stalled_days: 0,
+            document_id: None,
+            archived_at: None,
+            deleted_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn simulate(
+        requests: Vec<Request>,
+        agreed: &[Uuid],
+        changes: &[SimulatedChange],
+    ) -> Result<(TrustFactors, Vec<String>), AppError> {
+        let mut requests = requests.into_iter().map(|r| (r.id, r)).collect();
+        let mut factors = TrustFactors::default();
+        let notes = apply_changes(&mut factors, &mut requests, agreed, changes)?;
+        Ok((factors, notes))
+    }
+
+    #[test]
+    fn simulation_rejects_empty_oversized_and_out_of_range_changes() {
+        let body = |changes| SimulationBody { changes };
+        assert!(validate_simulation(&body(vec![])).is_err());
+        assert!(validate_simulation(&body((0..51).map(|_| SimulatedChange::AddPeers { count: 1 }).collect())).is_err());
+        assert!(validate_simulation(&body(vec![SimulatedChange::AddInteractions { count: 0 }])).is_err());
+        assert!(validate_simulation(&body(vec![SimulatedChange::AddPeers { count: 10_001 }])).is_err());
+        assert!(validate_simulation(&body(vec![SimulatedChange::AddPeers { count: 10_000 }])).is_ok());
+    }
+
+    #[test]
+    fn simulated_changes_follow_the_ledger_rules() {
+        let fair = request(RequestStatus::Fair);
+        let critical = request(RequestStatus::Critical);
+        let changes = [
+            SimulatedChange::CompleteRequest { request_id: fair.id },
+            SimulatedChange::ResolveRequest { request_id: critical.id },
+            SimulatedChange::AddInteractions { count: 4 },
+            SimulatedChange::AddPeers { count: 2 },
+        ];
+
+        let (factors, notes) = simulate(vec![fair, critical], &[], &changes).unwrap();
+        assert_eq!(factors.completed_requests, 1);
+        assert_eq!(factors.critical_requests, 0);
+        assert_eq!(factors.total_interactions, 4);
+        assert_eq!(factors.peer_count, 2);
+        // One note for the missing agreement, one for the unrefunded penalty.
+        assert_eq!(notes.len(), 2);
+    }
+
+    #[test]
+    fn simulated_changes_apply_in_order() {
+        let stalled = request(RequestStatus::Stalled);
+        let id = stalled.id;
+        let changes = [SimulatedChange::ResolveRequest { request_id: id }, SimulatedChange::CompleteRequest { request_id: id }];
+
+        let (factors, notes) = simulate(vec![stalled], &[id], &changes).unwrap();
+        assert_eq!(factors.completed_requests, 1);
+        assert_eq!(notes.len(), 1);
+
+        // Completed is terminal, so the second change can't be made.
+        let stalled = request(RequestStatus::Stalled);
+        let id = stalled.id;
+        let changes = [SimulatedChange::CompleteRequest { request_id: id }, SimulatedChange::ResolveRequest { request_id: id }];
+        assert!(simulate(vec![stalled], &[id], &changes).is_err());
+    }
+
+    #[test]
+    fn simulation_rejects_unknown_and_archived_requests() {
+        let changes = [SimulatedChange::CompleteRequest { request_id: Uuid::new_v4() }];
+        assert!(simulate(vec![], &[], &changes).is_err());
+
+        let mut archived = request(RequestStatus::Stalled);
+        archived.archived_at = Some(Utc::now());
+        let changes = [SimulatedChange::CompleteRequest { request_id: archived.id }];
+        assert!(simulate(vec![archived], &[], &changes).is_err());
+    }
+
+    #[test]
+    fn factor_diff_lists_only_changed_factors() {
+        let w = weights();
+        let current = TrustFactors { stalled_requests: 2, ..Default::default() };
+        let projected = TrustFactors { stalled_requests: 2, completed_requests: 3, total_interactions: 5, ..Default::default() };
 
-    match peers {
-        Ok(p) => HttpResponse::Ok().json(ApiResponse::ok(p)),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::err(&format!("Error: {}", e))),
+        let diff = factor_diff(&w, &current, &projected);
+        let points: Vec<(&str, i32)> = diff.iter().map(|d| (d.factor, d.points)).collect();
+        assert_eq!(points, vec![("completed_requests", 30), ("total_interactions", 2)]);
     }
 }
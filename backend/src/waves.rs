@@ -0,0 +1,297 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::AppError;
+use crate::models::*;
+
+/// Waves scoring at least this much are reported as strengths.
+const STRENGTH_THRESHOLD: i32 = 70;
+/// Score given to a wave there is no data for yet.
+const NEUTRAL: i32 = 50;
+
+fn ratio(part: i32, whole: i32) -> Option<f64> {
+    (whole > 0).then(|| part as f64 / whole as f64)
+}
+
+/// Computes the five waves (self, relationship, organizational, market and
+/// societal trust) from `inputs`.
+pub fn compute_waves(inputs: &WaveInputs) -> TrustWaves {
+    // Self: keeping commitments. Completed requests count fully, open ones
+    // half, and stalled or critical ones not at all.
+    let open = inputs.requests_total - inputs.requests_completed - inputs.requests_stalled - inputs.requests_critical;
+    let reliability = ratio(2 * inputs.requests_completed + open, 2 * inputs.requests_total);
+    let self_trust = reliability.map_or(NEUTRAL, |r| (r * 100.0).round() as i32) - (inputs.open_alerts * 5).min(20);
+
+    let relationship_trust = inputs.relationship_score.unwrap_or(NEUTRAL);
+
+    // Organizational: how often negotiated agreements end in acceptance.
+    let organizational_trust = ratio(inputs.agreements_accepted, inputs.agreements_accepted + inputs.agreements_rejected)
+        .map_or(NEUTRAL, |r| (r * 100.0).round() as i32);
+
+    // Market: reputation, from the trust score and how widely known the
+    // subject is.
+    let market_trust = inputs.trust_score.map_or(NEUTRAL, |s| s / 10) + inputs.market_reach.min(5) * 4;
+
+    // Societal: contribution beyond one's own goals.
+    let societal_trust = inputs.activity_points / 3 + inputs.requests_completed * 10;
+
+    TrustWaves {
+        self_trust: self_trust.clamp(0, 100),
+        relationship_trust: relationship_trust.clamp(0, 100),
+        organizational_trust: organizational_trust.clamp(0, 100),
+        market_trust: market_trust.clamp(0, 100),
+        societal_trust: societal_trust.clamp(0, 100),
+    }
+}
+
+fn strengths(waves: &TrustWaves) -> Vec<&'static str> {
+    let mut ranked = vec![
+        ("self", waves.self_trust),
+        ("relationship", waves.relationship_trust),
+        ("organizational", waves.organizational_trust),
+        ("market", waves.market_trust),
+        ("societal", waves.societal_trust),
+    ];
+    ranked.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    ranked
+        .into_iter()
+        .filter(|(_, score)| *score >= STRENGTH_THRESHOLD)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+fn profile(peer_id: Option<Uuid>, peer_name: Option<String>, inputs: WaveInputs) -> TrustWaveProfile {
+    let waves = compute_waves(&inputs);
+    let overall = (waves.self_trust
+        + waves.relationship_trust
+        + waves.organizational_trust
+        + waves.market_trust
+        + waves.societal_trust)
+        / 5;
+
+    TrustWaveProfile {
+        peer_id,
+        peer_name,
+        waves,
+        overall,
+        strengths: strengths(&waves),
+        inputs: Some(inputs),
+        computed_at: Utc::now(),
+    }
+}
+
+async fn load_self_inputs(conn: &mut PgConnection, user_id: Uuid) -> Result<WaveInputs, sqlx::Error> {
+    sqlx::query_as::<_, WaveInputs>(
+        "SELECT rq.*, ag.*,
+                (SELECT COALESCE(SUM(interactions), 0)::int FROM network_peers WHERE user_id = u.id) AS interactions,
+                (SELECT ROUND(AVG(relationship_score))::int FROM network_peers WHERE user_id = u.id) AS relationship_score,
+                (SELECT COUNT(DISTINCT user_id)::int FROM network_peers
+                 WHERE peer_user_id = u.id) AS market_reach,
+                (SELECT COUNT(*)::int FROM alerts
+                 WHERE user_id = u.id AND NOT is_read AND alert_type = 'request') AS open_alerts,
+                ts.score AS trust_score,
+                (SELECT COALESCE(SUM(quantity), 0)::int FROM trust_events
+                 WHERE user_id = u.id AND event_type = 'activity') AS activity_points
+         FROM users u
+         LEFT JOIN trust_scores ts ON ts.user_id = u.id
+         CROSS JOIN LATERAL (
+             SELECT COUNT(*)::int AS requests_total,
+                    (COUNT(*) FILTER (WHERE r.status = 'completed'))::int AS requests_completed,
+                    (COUNT(*) FILTER (WHERE r.status = 'stalled'))::int AS requests_stalled,
+                    (COUNT(*) FILTER (WHERE r.status = 'critical'))::int AS requests_critical
+             FROM requests r
+             WHERE r.user_id = u.id AND r.deleted_at IS NULL
+         ) rq
+         CROSS JOIN LATERAL (
+             SELECT (COUNT(*) FILTER (WHERE a.status = 'accepted'))::int AS agreements_accepted,
+                    (COUNT(*) FILTER (WHERE a.status = 'rejected'))::int AS agreements_rejected,
+                    (COUNT(*) FILTER (WHERE a.status IN ('proposed', 'countered')))::int AS agreements_pending
+             FROM agreements a
+             JOIN requests ar ON ar.id = a.request_id
+             WHERE ar.deleted_at IS NULL
+               AND (ar.user_id = u.id OR EXISTS (
+                   SELECT 1 FROM network_peers anp
+                   WHERE anp.user_id = ar.user_id AND anp.peer_name = a.peer_name AND anp.peer_user_id = u.id
+               ))
+         ) ag
+         WHERE u.id = $1"
+    )
+    .bind(user_id)
+    .fetch_one(conn)
+    .await
+}
+
+#[derive(Debug, FromRow)]
+struct PeerWaveInputs {
+    peer_id: Uuid,
+    peer_name: String,
+    #[sqlx(flatten)]
+    inputs: WaveInputs,
+}
+
+/// Wave inputs for each of `user_id`'s peers, or just `peer_id` if given.
+/// Registered peers contribute their own trust score, activity and reach;
+/// everything else comes from the owner's requests the peer is tagged on.
+async fn load_peer_inputs(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    peer_id: Option<Uuid>,
+) -> Result<Vec<PeerWaveInputs>, sqlx::Error> {
+    sqlx::query_as::<_, PeerWaveInputs>(
+        "SELECT np.id AS peer_id, np.peer_name, rq.*, ag.*,
+                np.interactions,
+                np.relationship_score,
+                (SELECT COUNT(DISTINCT o.user_id)::int FROM network_peers o
+                 WHERE o.peer_user_id = np.peer_user_id AND o.user_id <> np.user_id) AS market_reach,
+                (SELECT COUNT(DISTINCT al.id)::int FROM alerts al
+                 JOIN request_peers arp ON arp.request_id = al.request_id
+                 WHERE al.user_id = np.user_id AND NOT al.is_read AND arp.peer_name = np.peer_name) AS open_alerts,
+                pts.score AS trust_score,
+                (SELECT COALESCE(SUM(quantity), 0)::int FROM trust_events
+                 WHERE user_id = np.peer_user_id AND event_type = 'activity') AS activity_points
+         FROM network_peers np
+         LEFT JOIN trust_scores pts ON pts.user_id = np.peer_user_id
+         CROSS JOIN LATERAL (
+             SELECT COUNT(*)::int AS requests_total,
+                    (COUNT(*) FILTER (WHERE r.status = 'completed'))::int AS requests_completed,
+                    (COUNT(*) FILTER (WHERE r.status = 'stalled'))::int AS requests_stalled,
+                    (COUNT(*) FILTER (WHERE r.status = 'critical'))::int AS requests_critical
+             FROM requests r
+             JOIN request_peers rp ON rp.request_id = r.id
+             WHERE r.user_id = np.user_id AND r.deleted_at IS NULL AND rp.peer_name = np.peer_name
+         ) rq
+         CROSS JOIN LATERAL (
+             SELECT (COUNT(*) FILTER (WHERE a.status = 'accepted'))::int AS agreements_accepted,
+                    (COUNT(*) FILTER (WHERE a.status = 'rejected'))::int AS agreements_rejected,
+                    (COUNT(*) FILTER (WHERE a.status IN ('proposed', 'countered')))::int AS agreements_pending
+             FROM agreements a
+             JOIN requests ar ON ar.id = a.request_id
+             WHERE ar.user_id = np.user_id AND ar.deleted_at IS NULL AND a.peer_name = np.peer_name
+         ) ag
+         WHERE np.user_id = $1 AND ($2::uuid IS NULL OR np.id = $2)
+         ORDER BY np.peer_name"
+    )
+    .bind(user_id)
+    .bind(peer_id)
+    .fetch_all(conn)
+    .await
+}
+
+/// Upserts `profiles` for `user_id`, leaving rows whose inputs haven't
+/// changed as they are.
+async fn store_profiles(conn: &mut PgConnection, user_id: Uuid, profiles: &[&TrustWaveProfile]) -> Result<(), sqlx::Error> {
+    // The user's own profile and their peers' are unique on different columns.
+    let targets = [(false, "(user_id) WHERE peer_id IS NULL"), (true, "(peer_id) WHERE peer_id IS NOT NULL")];
+    for (for_peers, conflict) in targets {
+        let group: Vec<&TrustWaveProfile> =
+            profiles.iter().copied().filter(|p| p.peer_id.is_some() == for_peers).collect();
+        if group.is_empty() {
+            continue;
+        }
+
+        let ids: Vec<Uuid> = group.iter().map(|_| Uuid::new_v4()).collect();
+        let peer_ids: Vec<Option<Uuid>> = group.iter().map(|p| p.peer_id).collect();
+        let wave = |f: fn(&TrustWaves) -> i32| -> Vec<i32> { group.iter().map(|p| f(&p.waves)).collect() };
+        let inputs: Vec<Json<&Option<WaveInputs>>> = group.iter().map(|p| Json(&p.inputs)).collect();
+        let computed_at: Vec<DateTime<Utc>> = group.iter().map(|p| p.computed_at).collect();
+
+        sqlx::query(&format!(
+            "INSERT INTO trust_wave_profiles AS t
+                (id, user_id, peer_id, self_trust, relationship_trust, organizational_trust,
+                 market_trust, societal_trust, inputs, computed_at)
+             SELECT id, $2, peer_id, self_trust, relationship_trust, organizational_trust,
+                    market_trust, societal_trust, inputs, computed_at
+             FROM UNNEST($1::uuid[], $3::uuid[], $4::int[], $5::int[], $6::int[], $7::int[], $8::int[],
+                         $9::jsonb[], $10::timestamptz[])
+                 AS p(id, peer_id, self_trust, relationship_trust, organizational_trust,
+                      market_trust, societal_trust, inputs, computed_at)
+             ON CONFLICT {} DO UPDATE SET
+                self_trust = EXCLUDED.self_trust, relationship_trust = EXCLUDED.relationship_trust,
+                organizational_trust = EXCLUDED.organizational_trust, market_trust = EXCLUDED.market_trust,
+                societal_trust = EXCLUDED.societal_trust, inputs = EXCLUDED.inputs, computed_at = EXCLUDED.computed_at
+             WHERE t.inputs IS DISTINCT FROM EXCLUDED.inputs",
+            conflict
+        ))
+        .bind(&ids)
+        .bind(user_id)
+        .bind(&peer_ids)
+        .bind(wave(|w| w.self_trust))
+        .bind(wave(|w| w.relationship_trust))
+        .bind(wave(|w| w.organizational_trust))
+        .bind(wave(|w| w.market_trust))
+        .bind(wave(|w| w.societal_trust))
+        .bind(&inputs)
+        .bind(&computed_at)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Recomputes and stores the user's own profile and every peer's, and
+/// returns them without their inputs for the network map.
+pub async fn get_wave_summary(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let mut tx = pool.begin().await?;
+
+    let mut own = profile(None, None, load_self_inputs(&mut tx, user_id).await?);
+    let mut peers: Vec<TrustWaveProfile> = load_peer_inputs(&mut tx, user_id, None)
+        .await?
+        .into_iter()
+        .map(|p| profile(Some(p.peer_id), Some(p.peer_name), p.inputs))
+        .collect();
+
+    let all: Vec<&TrustWaveProfile> = std::iter::once(&own).chain(&peers).collect();
+    store_profiles(&mut tx, user_id, &all).await?;
+    tx.commit().await?;
+
+    own.inputs = None;
+    for peer in &mut peers {
+        peer.inputs = None;
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(TrustWaveSummary { own, peers })))
+}
+
+pub async fn get_own_waves(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let mut tx = pool.begin().await?;
+
+    let own = profile(None, None, load_self_inputs(&mut tx, user_id).await?);
+    store_profiles(&mut tx, user_id, &[&own]).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(own)))
+}
+
+pub async fn get_peer_waves(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user.id;
+    let mut tx = pool.begin().await?;
+
+    let p = load_peer_inputs(&mut tx, user_id, Some(path.into_inner()))
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound("Peer"))?;
+
+    let peer = profile(Some(p.peer_id), Some(p.peer_name), p.inputs);
+    store_profiles(&mut tx, user_id, &[&peer]).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::ok(peer)))
+}